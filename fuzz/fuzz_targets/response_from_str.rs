@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serde_xmlrpc::Value;
+
+fuzz_target!(|data: &str| {
+    let _ = serde_xmlrpc::response_from_str::<Value>(data);
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serde_xmlrpc::Value;
+
+fuzz_target!(|data: &str| {
+    // Decoding is checked both into a Value and into a typed structure, and
+    // anything which decodes should encode again.
+    if let Ok(value) = serde_xmlrpc::value_from_str::<Value>(data) {
+        let _ = serde_xmlrpc::value_to_string(&value);
+    }
+    let _ = serde_xmlrpc::value_from_str::<(Option<String>, Vec<i64>, bool)>(data);
+});
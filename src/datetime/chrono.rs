@@ -0,0 +1,66 @@
+use chrono::{
+    DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, Offset, TimeZone, Timelike, Utc,
+};
+
+use super::{millis_from_nanos, Parts, XmlRpcDateTime};
+
+fn naive_to_parts(dt: &NaiveDateTime, offset: i32) -> Parts {
+    Parts {
+        year: dt.year(),
+        month: dt.month(),
+        day: dt.day(),
+        hour: dt.hour(),
+        minute: dt.minute(),
+        second: dt.second(),
+        millisecond: millis_from_nanos(dt.nanosecond()),
+        offset,
+    }
+}
+
+fn naive_from_parts(parts: &Parts) -> Result<NaiveDateTime, String> {
+    NaiveDate::from_ymd_opt(parts.year, parts.month, parts.day)
+        .and_then(|date| {
+            date.and_hms_milli_opt(parts.hour, parts.minute, parts.second, parts.millisecond)
+        })
+        .ok_or_else(|| "dateTime is out of range".to_string())
+}
+
+impl XmlRpcDateTime for NaiveDateTime {
+    fn to_iso8601(&self) -> iso8601::DateTime {
+        naive_to_parts(self, 0).to_iso8601()
+    }
+
+    fn from_iso8601(dt: &iso8601::DateTime) -> Result<Self, String> {
+        let parts = Parts::from_iso8601(dt)?;
+        parts.expect_local()?;
+        naive_from_parts(&parts)
+    }
+}
+
+impl XmlRpcDateTime for DateTime<FixedOffset> {
+    fn to_iso8601(&self) -> iso8601::DateTime {
+        naive_to_parts(&self.naive_local(), self.offset().local_minus_utc()).to_iso8601()
+    }
+
+    fn from_iso8601(dt: &iso8601::DateTime) -> Result<Self, String> {
+        let parts = Parts::from_iso8601(dt)?;
+        let offset =
+            FixedOffset::east_opt(parts.offset).ok_or_else(|| "invalid offset".to_string())?;
+        offset
+            .from_local_datetime(&naive_from_parts(&parts)?)
+            .single()
+            .ok_or_else(|| "dateTime is out of range".to_string())
+    }
+}
+
+impl XmlRpcDateTime for DateTime<Utc> {
+    fn to_iso8601(&self) -> iso8601::DateTime {
+        naive_to_parts(&self.naive_utc(), Utc.fix().local_minus_utc()).to_iso8601()
+    }
+
+    fn from_iso8601(dt: &iso8601::DateTime) -> Result<Self, String> {
+        DateTime::<FixedOffset>::from_iso8601(dt).map(|dt| dt.with_timezone(&Utc))
+    }
+}
+
+impl_value_conversions!(NaiveDateTime, DateTime<FixedOffset>, DateTime<Utc>);
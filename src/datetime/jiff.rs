@@ -0,0 +1,42 @@
+use std::convert::TryFrom;
+
+use jiff::civil::DateTime;
+
+use super::{millis_from_nanos, Parts, XmlRpcDateTime};
+
+impl XmlRpcDateTime for DateTime {
+    fn to_iso8601(&self) -> iso8601::DateTime {
+        Parts {
+            year: self.year() as i32,
+            month: self.month() as u32,
+            day: self.day() as u32,
+            hour: self.hour() as u32,
+            minute: self.minute() as u32,
+            second: self.second() as u32,
+            millisecond: millis_from_nanos(self.subsec_nanosecond() as u32),
+            offset: 0,
+        }
+        .to_iso8601()
+    }
+
+    fn from_iso8601(dt: &iso8601::DateTime) -> Result<Self, String> {
+        fn field<T: TryFrom<i64>>(value: impl Into<i64>) -> Result<T, String> {
+            T::try_from(value.into()).map_err(|_| "dateTime is out of range".to_string())
+        }
+
+        let parts = Parts::from_iso8601(dt)?;
+        parts.expect_local()?;
+        DateTime::new(
+            field(parts.year)?,
+            field(parts.month)?,
+            field(parts.day)?,
+            field(parts.hour)?,
+            field(parts.minute)?,
+            field(parts.second)?,
+            field(parts.millisecond as i64 * 1_000_000)?,
+        )
+        .map_err(|e| e.to_string())
+    }
+}
+
+impl_value_conversions!(DateTime);
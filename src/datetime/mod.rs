@@ -0,0 +1,472 @@
+//! Conversions between `<dateTime.iso8601>` values and date types.
+//!
+//! Internally, dates are represented as an [iso8601::DateTime], which is also
+//! what [Value::DateTime] holds. Any type which
+//! implements [XmlRpcDateTime] can be converted to and from that
+//! representation, and so can be written as a `<dateTime.iso8601>` tag using
+//! [as_datetime](crate::as_datetime) or [DateTime](crate::DateTime).
+//!
+//! Support for other date libraries is available behind cargo features:
+//!
+//! * `chrono`: `NaiveDateTime`, `DateTime<Utc>` and `DateTime<FixedOffset>`.
+//! * `time`: `PrimitiveDateTime` and `OffsetDateTime`.
+//! * `jiff`: `civil::DateTime`.
+//!
+//! Each of these types can also be converted to and from a [Value] with
+//! `From` and `TryFrom`.
+//!
+//! All of these follow the same policies:
+//!
+//! * **Timezones**: the XML-RPC spec doesn't define a timezone for dates, and
+//!   a missing offset can't be told apart from an offset of zero once parsed.
+//!   Dates without an offset are always written in the short form from the
+//!   spec (`19980717T14:08:55`), with a fractional part if there are
+//!   milliseconds (`19980717T14:08:55.123`). Types with an offset are written
+//!   with it unless it is zero, and a date without an offset is read as UTC.
+//!   Types without an offset can only be read from dates without one (or with
+//!   an offset of zero); anything else is an error rather than being silently
+//!   shifted.
+//! * **Fractional seconds**: only milliseconds are kept, anything more precise
+//!   is truncated. Leap seconds are clamped to the last millisecond of the
+//!   minute.
+
+use std::borrow::Cow;
+
+use iso8601::{Date, DateTime};
+use serde::de::{value::BorrowedStrDeserializer, IntoDeserializer};
+
+#[cfg(doc)]
+use crate::Value;
+use crate::{Error, Result};
+
+// Implements conversions to and from Value for date types, based on their
+// XmlRpcDateTime implementation.
+#[cfg(any(feature = "chrono", feature = "time", feature = "jiff"))]
+macro_rules! impl_value_conversions {
+    ($($ty:ty),*) => {$(
+        impl From<$ty> for crate::Value {
+            fn from(other: $ty) -> Self {
+                crate::Value::DateTime(other.to_iso8601())
+            }
+        }
+
+        impl<'a> std::convert::TryFrom<&'a crate::Value> for $ty {
+            type Error = ();
+            fn try_from(value: &'a crate::Value) -> Result<Self, Self::Error> {
+                match value {
+                    crate::Value::DateTime(dt) => <$ty>::from_iso8601(dt).map_err(|_| ()),
+                    _ => Err(()),
+                }
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "chrono")]
+mod chrono;
+#[cfg(feature = "jiff")]
+mod jiff;
+#[cfg(feature = "time")]
+mod time;
+
+/// The newtype struct name, and enum variant, which marks a dateTime value.
+pub(crate) const TOKEN: &str = "$serde_xmlrpc::private::DateTime";
+
+/// The newtype struct name which [Value](crate::Value) uses to ask
+/// deserializers for dateTime values to be marked.
+pub(crate) const VALUE_TOKEN: &str = "$serde_xmlrpc::private::Value";
+
+/// A date type which can be represented as a `<dateTime.iso8601>` value.
+pub trait XmlRpcDateTime: Sized {
+    /// Converts the date to its iso8601 representation.
+    fn to_iso8601(&self) -> DateTime;
+
+    /// Converts an iso8601 date to this type, returning a description of the
+    /// problem if it can't be represented.
+    fn from_iso8601(dt: &DateTime) -> std::result::Result<Self, String>;
+}
+
+impl XmlRpcDateTime for DateTime {
+    fn to_iso8601(&self) -> DateTime {
+        *self
+    }
+
+    fn from_iso8601(dt: &DateTime) -> std::result::Result<Self, String> {
+        Ok(*dt)
+    }
+}
+
+/// A type which [as_datetime](crate::as_datetime) and
+/// [DateTime](crate::DateTime) can be used with. This is implemented for every
+/// [XmlRpcDateTime], and for `String`, which is checked to be a valid date but
+/// is otherwise written and read as is.
+pub trait DateTimeField: Sized {
+    /// Returns the text to write for the date.
+    fn to_datetime_text(&self) -> std::result::Result<Cow<'_, str>, String>;
+
+    /// Converts the text of a date to this type.
+    fn from_datetime_text(text: &str) -> std::result::Result<Self, String>;
+}
+
+impl<T> DateTimeField for T
+where
+    T: XmlRpcDateTime,
+{
+    fn to_datetime_text(&self) -> std::result::Result<Cow<'_, str>, String> {
+        Ok(Cow::Owned(format(&self.to_iso8601())))
+    }
+
+    fn from_datetime_text(text: &str) -> std::result::Result<Self, String> {
+        T::from_iso8601(&parse(text)?)
+    }
+}
+
+impl DateTimeField for String {
+    fn to_datetime_text(&self) -> std::result::Result<Cow<'_, str>, String> {
+        parse(self)?;
+        Ok(Cow::Borrowed(self))
+    }
+
+    fn from_datetime_text(text: &str) -> std::result::Result<Self, String> {
+        parse(text)?;
+        Ok(text.to_string())
+    }
+}
+
+/// The individual fields of a date, with the day resolved to a calendar
+/// date and the offset in seconds.
+pub(crate) struct Parts {
+    pub(crate) year: i32,
+    pub(crate) month: u32,
+    pub(crate) day: u32,
+    pub(crate) hour: u32,
+    pub(crate) minute: u32,
+    pub(crate) second: u32,
+    pub(crate) millisecond: u32,
+    pub(crate) offset: i32,
+}
+
+impl Parts {
+    pub(crate) fn from_iso8601(dt: &DateTime) -> std::result::Result<Self, String> {
+        let (year, month, day) = match dt.date {
+            Date::YMD { year, month, day } => (year, month, day),
+            Date::Week { year, ww, d } => {
+                if !(1..=53).contains(&ww) || !(1..=7).contains(&d) {
+                    return Err(format!("invalid week date: {}", dt.date));
+                }
+
+                // Week 1 is the week containing the 4th of January.
+                let jan4 = days_from_civil(year, 1, 4);
+                let monday = jan4 - (jan4 + 3).rem_euclid(7);
+                civil_from_days(monday + (ww as i64 - 1) * 7 + (d as i64 - 1))
+            }
+            Date::Ordinal { year, ddd } => {
+                if !(1..=366).contains(&ddd) {
+                    return Err(format!("invalid ordinal date: {}", dt.date));
+                }
+
+                civil_from_days(days_from_civil(year, 1, 1) + (ddd as i64 - 1))
+            }
+        };
+
+        Ok(Parts {
+            year,
+            month,
+            day,
+            hour: dt.time.hour,
+            minute: dt.time.minute,
+            second: dt.time.second,
+            millisecond: dt.time.millisecond,
+            offset: dt.time.tz_offset_hours * 3600 + dt.time.tz_offset_minutes * 60,
+        })
+    }
+
+    #[cfg(any(feature = "chrono", feature = "time", feature = "jiff"))]
+    pub(crate) fn to_iso8601(&self) -> DateTime {
+        DateTime {
+            date: Date::YMD {
+                year: self.year,
+                month: self.month,
+                day: self.day,
+            },
+            time: iso8601::Time {
+                hour: self.hour,
+                minute: self.minute,
+                second: self.second,
+                millisecond: self.millisecond,
+                tz_offset_hours: self.offset / 3600,
+                tz_offset_minutes: self.offset % 3600 / 60,
+            },
+        }
+    }
+
+    /// Returns an error if the date has an offset, for types which can't
+    /// represent one.
+    #[cfg(any(feature = "chrono", feature = "time", feature = "jiff"))]
+    pub(crate) fn expect_local(&self) -> std::result::Result<(), String> {
+        if self.offset != 0 {
+            return Err("dateTime has a timezone offset".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Converts nanoseconds to milliseconds, following the policy for fractional
+/// and leap seconds.
+#[cfg(any(feature = "chrono", feature = "time", feature = "jiff"))]
+pub(crate) fn millis_from_nanos(nanos: u32) -> u32 {
+    (nanos / 1_000_000).min(999)
+}
+
+// Days since 1970-01-01 for a date in the proleptic Gregorian calendar, and
+// its inverse. See http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let y = year as i64 - if month <= 2 { 1 } else { 0 };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as i32, month, day)
+}
+
+/// Formats a date the way the XML-RPC spec shows it (`19980717T14:08:55`)
+/// where possible, falling back to the extended ISO 8601 format for anything
+/// which can't be represented that way.
+pub(crate) fn format(dt: &DateTime) -> String {
+    match Parts::from_iso8601(dt) {
+        Ok(p) if (0..=9999).contains(&p.year) && p.offset == 0 => {
+            let mut text = format!(
+                "{:04}{:02}{:02}T{:02}:{:02}:{:02}",
+                p.year, p.month, p.day, p.hour, p.minute, p.second
+            );
+            if p.millisecond != 0 {
+                text.push_str(&format!(".{:03}", p.millisecond));
+            }
+            text
+        }
+        _ => dt.to_string(),
+    }
+}
+
+pub(crate) fn parse(text: &str) -> std::result::Result<DateTime, String> {
+    iso8601::datetime(text)
+}
+
+/// An enum with only the private dateTime variant, holding the date text.
+///
+/// Structs are passed on to visitors as maps, and none of our deserializers
+/// produce an enum when asked for any value, so a document can't forge this.
+pub(crate) struct DateTimeAccess<'de> {
+    text: Cow<'de, str>,
+}
+
+impl<'de> DateTimeAccess<'de> {
+    pub(crate) fn new(text: Cow<'de, str>) -> Self {
+        DateTimeAccess { text }
+    }
+}
+
+impl<'de> serde::de::EnumAccess<'de> for DateTimeAccess<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(BorrowedStrDeserializer::<Error>::new(TOKEN))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> serde::de::VariantAccess<'de> for DateTimeAccess<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Err(serde::de::Error::custom("expected the dateTime text"))
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        match self.text {
+            Cow::Borrowed(text) => seed.deserialize(BorrowedStrDeserializer::new(text)),
+            Cow::Owned(text) => seed.deserialize(text.into_deserializer()),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        Err(serde::de::Error::custom("expected the dateTime text"))
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        Err(serde::de::Error::custom("expected the dateTime text"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(any(feature = "chrono", feature = "time", feature = "jiff"))]
+    use crate::Value;
+
+    #[test]
+    fn week_and_ordinal_dates() {
+        for (text, expected) in [
+            ("2009-W01-1T00:00:00", (2008, 12, 29)),
+            ("2009-W53-7T00:00:00", (2010, 1, 3)),
+            ("2004-W53-6T00:00:00", (2005, 1, 1)),
+            ("1981-095T00:00:00", (1981, 4, 5)),
+            ("2000-366T00:00:00", (2000, 12, 31)),
+        ] {
+            let parts = Parts::from_iso8601(&parse(text).unwrap()).unwrap();
+            assert_eq!((parts.year, parts.month, parts.day), expected, "{}", text);
+        }
+
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+
+        // Week dates are normalized when written.
+        assert_eq!(
+            format(&parse("2009-W01-1T10:00:00").unwrap()),
+            "20081229T10:00:00"
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_conversions() {
+        use ::chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+        use std::convert::TryFrom;
+
+        let naive = NaiveDate::from_ymd_opt(1998, 7, 17)
+            .unwrap()
+            .and_hms_nano_opt(14, 8, 55, 123_456_789)
+            .unwrap();
+        let val = Value::from(naive);
+        assert_eq!(format(&val.as_datetime().unwrap()), "19980717T14:08:55.123");
+        assert_eq!(
+            NaiveDateTime::try_from(&val).unwrap(),
+            naive.with_nanosecond(123_000_000).unwrap()
+        );
+        let val = Value::DateTime(parse(&format(&val.as_datetime().unwrap())).unwrap());
+        assert_eq!(
+            NaiveDateTime::try_from(&val).unwrap(),
+            naive.with_nanosecond(123_000_000).unwrap()
+        );
+
+        let offset = FixedOffset::east_opt(2 * 3600).unwrap();
+        let dt = offset
+            .from_local_datetime(&naive.with_nanosecond(0).unwrap())
+            .unwrap();
+        let val = Value::from(dt);
+        assert_eq!(
+            format(&val.as_datetime().unwrap()),
+            "1998-07-17T14:08:55.000+02:00"
+        );
+        assert_eq!(DateTime::<FixedOffset>::try_from(&val).unwrap(), dt);
+        assert_eq!(
+            DateTime::<Utc>::try_from(&val).unwrap(),
+            dt.with_timezone(&Utc)
+        );
+
+        // A date with an offset can't be read as a naive date.
+        assert!(NaiveDateTime::try_from(&val).is_err());
+
+        // A date without an offset is read as UTC.
+        let val = Value::DateTime(parse("19980717T14:08:55").unwrap());
+        assert_eq!(
+            DateTime::<Utc>::try_from(&val).unwrap(),
+            Utc.with_ymd_and_hms(1998, 7, 17, 14, 8, 55).unwrap()
+        );
+        assert_eq!(
+            Value::from(Utc.with_ymd_and_hms(1998, 7, 17, 14, 8, 55).unwrap()),
+            val
+        );
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn time_conversions() {
+        use ::time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+        use std::convert::TryFrom;
+
+        let date = Date::from_calendar_date(1998, Month::July, 17).unwrap();
+        let datetime = |h, m, s, micro| {
+            PrimitiveDateTime::new(date, Time::from_hms_micro(h, m, s, micro).unwrap())
+        };
+
+        let dt = datetime(14, 8, 55, 123_456);
+        let val = Value::from(dt);
+        assert_eq!(format(&val.as_datetime().unwrap()), "19980717T14:08:55.123");
+        assert_eq!(
+            PrimitiveDateTime::try_from(&val).unwrap(),
+            datetime(14, 8, 55, 123_000)
+        );
+        let val = Value::DateTime(parse(&format(&val.as_datetime().unwrap())).unwrap());
+        assert_eq!(
+            PrimitiveDateTime::try_from(&val).unwrap(),
+            datetime(14, 8, 55, 123_000)
+        );
+
+        let offset = UtcOffset::from_hms(-5, -30, 0).unwrap();
+        let dt = datetime(14, 8, 55, 0).assume_offset(offset);
+        let val = Value::from(dt);
+        assert_eq!(
+            format(&val.as_datetime().unwrap()),
+            "1998-07-17T14:08:55.000-05:30"
+        );
+        assert_eq!(OffsetDateTime::try_from(&val).unwrap(), dt);
+        assert!(PrimitiveDateTime::try_from(&val).is_err());
+
+        let val = Value::DateTime(parse("19980717T14:08:55").unwrap());
+        assert_eq!(
+            OffsetDateTime::try_from(&val).unwrap(),
+            datetime(14, 8, 55, 0).assume_utc()
+        );
+    }
+
+    #[cfg(feature = "jiff")]
+    #[test]
+    fn jiff_conversions() {
+        use ::jiff::civil::{date, DateTime};
+        use std::convert::TryFrom;
+
+        let dt = date(1998, 7, 17).at(14, 8, 55, 123_456_789);
+        let val = Value::from(dt);
+        assert_eq!(format(&val.as_datetime().unwrap()), "19980717T14:08:55.123");
+        assert_eq!(
+            DateTime::try_from(&val).unwrap(),
+            date(1998, 7, 17).at(14, 8, 55, 123_000_000)
+        );
+        let val = Value::DateTime(parse(&format(&val.as_datetime().unwrap())).unwrap());
+        assert_eq!(
+            DateTime::try_from(&val).unwrap(),
+            date(1998, 7, 17).at(14, 8, 55, 123_000_000)
+        );
+
+        let val = Value::DateTime(parse("19980717T14:08:55+01:00").unwrap());
+        assert!(DateTime::try_from(&val).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use std::convert::TryFrom;
+
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+use super::{millis_from_nanos, Parts, XmlRpcDateTime};
+
+fn primitive_to_parts(dt: &PrimitiveDateTime, offset: i32) -> Parts {
+    Parts {
+        year: dt.year(),
+        month: dt.month() as u32,
+        day: dt.day() as u32,
+        hour: dt.hour() as u32,
+        minute: dt.minute() as u32,
+        second: dt.second() as u32,
+        millisecond: millis_from_nanos(dt.nanosecond()),
+        offset,
+    }
+}
+
+fn primitive_from_parts(parts: &Parts) -> Result<PrimitiveDateTime, String> {
+    fn field<T: TryFrom<u32>>(value: u32) -> Result<T, String> {
+        T::try_from(value).map_err(|_| "dateTime is out of range".to_string())
+    }
+
+    let month = Month::try_from(field::<u8>(parts.month)?).map_err(|e| e.to_string())?;
+    let date = Date::from_calendar_date(parts.year, month, field(parts.day)?)
+        .map_err(|e| e.to_string())?;
+    let time = Time::from_hms_milli(
+        field(parts.hour)?,
+        field(parts.minute)?,
+        field(parts.second)?,
+        field(parts.millisecond)?,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(PrimitiveDateTime::new(date, time))
+}
+
+impl XmlRpcDateTime for PrimitiveDateTime {
+    fn to_iso8601(&self) -> iso8601::DateTime {
+        primitive_to_parts(self, 0).to_iso8601()
+    }
+
+    fn from_iso8601(dt: &iso8601::DateTime) -> Result<Self, String> {
+        let parts = Parts::from_iso8601(dt)?;
+        parts.expect_local()?;
+        primitive_from_parts(&parts)
+    }
+}
+
+impl XmlRpcDateTime for OffsetDateTime {
+    fn to_iso8601(&self) -> iso8601::DateTime {
+        let local = PrimitiveDateTime::new(self.date(), self.time());
+        primitive_to_parts(&local, self.offset().whole_seconds()).to_iso8601()
+    }
+
+    fn from_iso8601(dt: &iso8601::DateTime) -> Result<Self, String> {
+        let parts = Parts::from_iso8601(dt)?;
+        let offset = UtcOffset::from_whole_seconds(parts.offset).map_err(|e| e.to_string())?;
+        Ok(primitive_from_parts(&parts)?.assume_offset(offset))
+    }
+}
+
+impl_value_conversions!(PrimitiveDateTime, OffsetDateTime);
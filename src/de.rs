@@ -1,136 +1,670 @@
+//! Deserialize xmlrpc data to a Rust data structure.
+
 use base64::prelude::*;
 use quick_xml::{events::Event, name::QName, Reader};
+use serde::de::IntoDeserializer;
 use serde::forward_to_deserialize_any;
-use std::convert::TryInto;
+use std::borrow::Cow;
+use std::convert::{TryFrom, TryInto};
+use std::io::{BufReader, Read};
 
+use crate::datetime::{self, DateTimeAccess};
 use crate::error::DecodingError;
-use crate::xml_ext::ReaderExt;
-use crate::{Error, Result};
+pub use crate::read::{IoReader, SliceReader, XmlRead};
+use crate::xml_ext::{is_whitespace, unexpected, ReaderExt};
+use crate::{Error, Result, Value};
+
+/// Options controlling how xmlrpc data is decoded. The defaults only accept
+/// what the XML-RPC spec describes.
+/// ```
+/// use serde_xmlrpc::{DecoderOptions, Value};
+///
+/// let body = r#"<value xmlns:ex="http://ws.apache.org/xmlrpc/namespaces/extensions"><ex:i8>42</ex:i8></value>"#;
+///
+/// assert!(serde_xmlrpc::value_from_str::<Value>(body).is_err());
+///
+/// let options = DecoderOptions::new().extensions(true);
+/// let val: Value = serde_xmlrpc::value_from_str_with(body, &options).unwrap();
+/// assert_eq!(val, Value::Int64(42));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DecoderOptions {
+    pub(crate) extensions: bool,
+    pub(crate) boolean_words: bool,
+    pub(crate) comma_decimals: bool,
+    pub(crate) ignore_tag_case: bool,
+    pub(crate) name_after_value: bool,
+    pub(crate) validate: bool,
+    pub(crate) allow_doctype: bool,
+    pub(crate) limits: Limits,
+}
+
+impl DecoderOptions {
+    /// Creates options which follow the XML-RPC spec.
+    pub fn new() -> Self {
+        DecoderOptions::default()
+    }
+
+    /// Creates options which accept the common deviations from the spec that
+    /// some servers produce. Each of them can also be enabled on its own:
+    ///
+    /// * [boolean_words](DecoderOptions::boolean_words): `<boolean>true</boolean>`
+    /// * [comma_decimals](DecoderOptions::comma_decimals): `<double>1,5</double>`
+    /// * [ignore_tag_case](DecoderOptions::ignore_tag_case): `<VALUE><INT>1</INT></VALUE>`
+    /// * [name_after_value](DecoderOptions::name_after_value): `<member>` with
+    ///   its `<name>` after the `<value>`
+    ///
+    /// Extension types are not part of this and need to be enabled separately.
+    /// ```
+    /// use serde_xmlrpc::DecoderOptions;
+    ///
+    /// let body = "<VALUE><Boolean> true </Boolean></VALUE>";
+    ///
+    /// assert!(serde_xmlrpc::value_from_str::<bool>(body).is_err());
+    ///
+    /// let options = DecoderOptions::lenient();
+    /// assert!(serde_xmlrpc::value_from_str_with::<bool>(body, &options).unwrap());
+    /// ```
+    pub fn lenient() -> Self {
+        DecoderOptions::new()
+            .boolean_words(true)
+            .comma_decimals(true)
+            .ignore_tag_case(true)
+            .name_after_value(true)
+    }
+
+    /// Creates options which check the whole document against the XML-RPC
+    /// grammar, see [validate](DecoderOptions::validate).
+    /// ```
+    /// use serde_xmlrpc::DecoderOptions;
+    ///
+    /// let body = "<value><int>1</int></value><value/>";
+    ///
+    /// assert_eq!(serde_xmlrpc::value_from_str::<i32>(body).unwrap(), 1);
+    ///
+    /// let err = serde_xmlrpc::value_from_str_with::<i32>(body, &DecoderOptions::strict()).unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "decoding error: invalid XML-RPC: unexpected <value> after the document element"
+    /// );
+    /// ```
+    pub fn strict() -> Self {
+        DecoderOptions::new().validate(true)
+    }
+
+    /// Accepts the extension types used by Apache ws-xmlrpc: `ex:i1`, `ex:i2`,
+    /// `ex:i8`, `ex:float`, `ex:nil`, `ex:dateTime`, `ex:biginteger`,
+    /// `ex:bigdecimal` and `ex:serializable`. These are recognized by the
+    /// namespace URI the prefix is bound to, not by the prefix itself.
+    ///
+    /// As there are no matching Rust types, `ex:biginteger` and
+    /// `ex:bigdecimal` are decoded as strings, and `ex:serializable` is
+    /// decoded as binary data.
+    pub fn extensions(mut self, enabled: bool) -> Self {
+        self.extensions = enabled;
+        self
+    }
+
+    /// Accepts `true` and `false` in any case as booleans, in addition to
+    /// `1` and `0`.
+    pub fn boolean_words(mut self, enabled: bool) -> Self {
+        self.boolean_words = enabled;
+        self
+    }
+
+    /// Accepts a comma as the decimal separator of doubles, as long as there
+    /// is no period in the number as well.
+    pub fn comma_decimals(mut self, enabled: bool) -> Self {
+        self.comma_decimals = enabled;
+        self
+    }
+
+    /// Accepts the XML-RPC element names in any case, such as `<VALUE>` or
+    /// `<methodresponse>`. Extension types still need to match exactly.
+    pub fn ignore_tag_case(mut self, enabled: bool) -> Self {
+        self.ignore_tag_case = enabled;
+        self
+    }
+
+    /// Accepts struct members with the `<name>` after the `<value>`. As the
+    /// value has to be read before its name is known, it can't borrow from
+    /// the input in that case.
+    pub fn name_after_value(mut self, enabled: bool) -> Self {
+        self.name_after_value = enabled;
+        self
+    }
+
+    /// Rejects anything the XML-RPC grammar doesn't allow, rather than
+    /// skipping over what isn't needed. Errors describe exactly which rule
+    /// was broken, which makes this useful for testing servers. On top of
+    /// the usual checks this rejects:
+    ///
+    /// * an XML declaration anywhere but at the start
+    /// * attributes other than namespace declarations
+    /// * unknown elements or text where the grammar doesn't allow them, for
+    ///   example after the `<value>` of a `<param>` or inside `<nil/>`
+    /// * anything but comments and processing instructions after the
+    ///   document element
+    /// * responses with more than one `<param>`
+    /// * faults which aren't a struct of exactly an `<int>` faultCode and a
+    ///   string faultString
+    /// * arrays with more elements than the type they are decoded into
+    /// * method names with characters other than letters, digits, `_`, `.`,
+    ///   `:` and `/`
+    /// * `<int>` and `<i4>` values which don't fit in 32 bits
+    /// * `<double>` values which aren't plain decimals, like `1e5` or `NaN`
+    /// * malformed `<dateTime.iso8601>` values
+    ///
+    /// The deviations accepted by the other options are still accepted when
+    /// they are enabled as well.
+    pub fn validate(mut self, enabled: bool) -> Self {
+        self.validate = enabled;
+        self
+    }
+
+    /// Skips document type declarations rather than rejecting them. XML-RPC
+    /// has no use for them, and they are the source of attacks like
+    /// external entities and exponential entity expansion, so only enable
+    /// this for servers which send them regardless.
+    ///
+    /// Entities declared in a DTD are never expanded, so references to them
+    /// are still rejected, as is any other entity besides the predefined
+    /// ones (`&lt;`, `&gt;`, `&amp;`, `&apos;` and `&quot;`) and character
+    /// references.
+    /// ```
+    /// use serde_xmlrpc::DecoderOptions;
+    ///
+    /// let body = r#"<!DOCTYPE value SYSTEM "value.dtd"><value><int>1</int></value>"#;
+    ///
+    /// let err = serde_xmlrpc::value_from_str::<i32>(body).unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "decoding error: invalid XML-RPC: document type declarations are not allowed"
+    /// );
+    ///
+    /// let options = DecoderOptions::new().allow_doctype(true);
+    /// assert_eq!(serde_xmlrpc::value_from_str_with::<i32>(body, &options).unwrap(), 1);
+    /// ```
+    pub fn allow_doctype(mut self, enabled: bool) -> Self {
+        self.allow_doctype = enabled;
+        self
+    }
+
+    /// Sets the limits on the size of decoded documents, see [Limits].
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+}
+
+/// Limits on the size of decoded documents, to protect against untrusted
+/// input. Exceeding any of them results in a
+/// [LimitExceeded](DecodingError::LimitExceeded) error.
+///
+/// By default only the nesting depth is limited, to 64 arrays and structs,
+/// as every level of nesting is decoded recursively. Everything else is
+/// unlimited.
+/// ```
+/// use serde_xmlrpc::{DecoderOptions, DecodingError, Limits};
+///
+/// let body = "<methodCall><methodName>ping</methodName><params>
+///   <param><value><int>1</int></value></param>
+///   <param><value><int>2</int></value></param>
+/// </params></methodCall>";
+///
+/// let options = DecoderOptions::new().limits(Limits::new().max_params(1));
+/// let err = serde_xmlrpc::request_from_str_with(body, &options).unwrap_err();
+/// assert!(matches!(
+///     err,
+///     serde_xmlrpc::Error::DecodingError(DecodingError::LimitExceeded("params", 1))
+/// ));
+/// assert_eq!(err.to_string(), "decoding error: limit exceeded: more than 1 params");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Limits {
+    pub(crate) max_depth: usize,
+    pub(crate) max_params: usize,
+    pub(crate) max_members: usize,
+    pub(crate) max_elements: usize,
+    pub(crate) max_string_bytes: usize,
+    pub(crate) max_base64_bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_depth: 64,
+            max_params: usize::MAX,
+            max_members: usize::MAX,
+            max_elements: usize::MAX,
+            max_string_bytes: usize::MAX,
+            max_base64_bytes: usize::MAX,
+        }
+    }
+}
+
+impl Limits {
+    /// Creates the default limits.
+    pub fn new() -> Self {
+        Limits::default()
+    }
+
+    /// Sets how deeply arrays and structs may be nested. A value which
+    /// isn't an array or struct has a depth of 0.
+    pub fn max_depth(mut self, max: usize) -> Self {
+        self.max_depth = max;
+        self
+    }
+
+    /// Sets how many `<param>` elements a request or response may have.
+    pub fn max_params(mut self, max: usize) -> Self {
+        self.max_params = max;
+        self
+    }
+
+    /// Sets how many members a single struct may have.
+    pub fn max_members(mut self, max: usize) -> Self {
+        self.max_members = max;
+        self
+    }
+
+    /// Sets how many elements a single array may have.
+    pub fn max_elements(mut self, max: usize) -> Self {
+        self.max_elements = max;
+        self
+    }
+
+    /// Sets the maximum length in bytes of strings, member names and method
+    /// names, after unescaping.
+    pub fn max_string_bytes(mut self, max: usize) -> Self {
+        self.max_string_bytes = max;
+        self
+    }
+
+    /// Sets the maximum length in bytes of decoded `<base64>` data.
+    pub fn max_base64_bytes(mut self, max: usize) -> Self {
+        self.max_base64_bytes = max;
+        self
+    }
+}
+
+/// Returns a [LimitExceeded](DecodingError::LimitExceeded) error if `count`
+/// is above `max`.
+pub(crate) fn check_limit(what: &'static str, count: usize, max: usize) -> Result<()> {
+    if count > max {
+        return Err(DecodingError::LimitExceeded(what, max).into());
+    }
+    Ok(())
+}
+
+/// A structure that deserializes xmlrpc values into Rust values.
+///
+/// Each call to `deserialize` reads a single `<value>` element from the
+/// underlying [XmlRead] source. Any quick-xml [Reader] can be used as a
+/// source by wrapping it in a [SliceReader] or [IoReader], which makes it
+/// possible to decode values embedded in a larger XML document.
+/// ```
+/// use serde::Deserialize;
+/// use serde_xmlrpc::de::{Deserializer, SliceReader};
+///
+/// let mut reader = quick_xml::Reader::from_str("<value><int>42</int></value>");
+/// let mut de = Deserializer::new(SliceReader::new(reader));
+/// let x = i32::deserialize(&mut de).unwrap();
+/// assert_eq!(x, 42);
+///
+/// // The reader can be taken back out to continue reading the document.
+/// let reader = de.into_inner().into_inner();
+/// ```
+#[derive(Clone)]
+pub struct Deserializer<R> {
+    pub(crate) read: R,
+}
+
+impl<R> Deserializer<R> {
+    /// Creates a deserializer reading from the given [XmlRead] source.
+    pub fn new(read: R) -> Self {
+        Deserializer { read }
+    }
+
+    /// Unwraps the underlying [XmlRead] source.
+    pub fn into_inner(self) -> R {
+        self.read
+    }
+}
+
+impl<'de> Deserializer<SliceReader<'de>> {
+    /// Creates a deserializer which borrows from a string.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &'de str) -> Self {
+        Deserializer::new(SliceReader::from_str(input))
+    }
+
+    /// Creates a deserializer which borrows from raw bytes, decoding them as
+    /// declared by a byte order mark or the XML declaration.
+    pub fn from_slice(input: &'de [u8]) -> Self {
+        Deserializer::new(SliceReader::from_slice(input))
+    }
+}
+
+impl<R> Deserializer<IoReader<BufReader<R>>>
+where
+    R: Read,
+{
+    /// Creates a deserializer which reads from an `io::Read` source.
+    pub fn from_reader(reader: R) -> Self {
+        Deserializer::new(IoReader::new(Reader::from_reader(BufReader::new(reader))))
+    }
+}
+
+impl<'de, R> serde::Deserializer<'de> for &mut Deserializer<R>
+where
+    R: XmlRead<'de>,
+{
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.read.expect_tag(QName(b"value"))?;
+        ValueDeserializer::new(&mut self.read)?.deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.read.expect_tag(QName(b"value"))?;
+        ValueDeserializer::new(&mut self.read)?.deserialize_option(visitor)
+    }
 
-pub(crate) struct Deserializer<'a, 'r> {
-    reader: &'a mut Reader<&'r [u8]>,
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.read.expect_tag(QName(b"value"))?;
+        ValueDeserializer::new(&mut self.read)?.deserialize_newtype_struct(name, visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.read.expect_tag(QName(b"value"))?;
+        ValueDeserializer::new(&mut self.read)?.deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_deserialize_any!(
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
+        byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    );
+}
+
+/// Deserializes the contents of a `<value>` tag. This is supposed to be used
+/// after the `<value>` start tag was read.
+struct ValueDeserializer<'a, 'de, R> {
+    reader: &'a mut R,
+    // Any text which was already read from the start of the value.
+    text: Option<Cow<'de, str>>,
+    // Whether dateTime values should be passed on as a marked map rather
+    // than a string. This is only requested when deserializing a Value.
+    mark_datetime: bool,
 }
 
-impl<'a, 'r> Deserializer<'a, 'r> {
-    pub(crate) fn new(reader: &'a mut Reader<&'r [u8]>) -> Result<Self> {
-        let ret = Deserializer { reader };
+impl<'a, 'de, R> ValueDeserializer<'a, 'de, R>
+where
+    R: XmlRead<'de>,
+{
+    fn new(reader: &'a mut R) -> Result<Self> {
+        let ret = ValueDeserializer {
+            reader,
+            text: None,
+            mark_datetime: false,
+        };
         Ok(ret)
     }
+
+    fn visit_datetime<V>(&mut self, end: QName, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let text = self.reader.read_trimmed_text(end)?;
+        if self.reader.options().validate {
+            datetime::parse(&text).map_err(|_| {
+                DecodingError::InvalidDocument(format!(
+                    "<{}> value {:?} is not a valid date and time",
+                    String::from_utf8_lossy(end.into_inner()),
+                    text
+                ))
+            })?;
+        }
+        if self.mark_datetime {
+            visitor.visit_enum(DateTimeAccess::new(text))
+        } else {
+            visit_cow_str(visitor, text)
+        }
+    }
+
+    fn visit_base64<V>(&mut self, end: QName, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // Encoders commonly wrap base64 data into lines, so any whitespace is
+        // removed before decoding.
+        let mut text = self.reader.read_text(end)?.into_owned();
+        text.retain(|c| !matches!(c, ' ' | '\t' | '\r' | '\n'));
+        // Check the decoded size before allocating for it. Every four
+        // characters hold three bytes, less one for each padding character.
+        let padding = text.bytes().rev().take(2).filter(|&b| b == b'=').count();
+        let max = self.reader.options().limits.max_base64_bytes;
+        check_limit(
+            "bytes of base64 data",
+            (text.len() / 4 * 3).saturating_sub(padding),
+            max,
+        )?;
+        let data = BASE64_STANDARD.decode(text).map_err(DecodingError::from)?;
+        visitor.visit_byte_buf(data)
+    }
+
+    fn visit_nil<V>(&mut self, end: QName, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.reader.read_to_end(end)?;
+        visitor.visit_unit()
+    }
+
+    /// Reads the text of a floating point number, up to the closing `end`
+    /// tag.
+    fn read_float_text(&mut self, end: QName) -> Result<Cow<'de, str>> {
+        let text = self.reader.read_trimmed_text(end)?;
+        if self.reader.options().comma_decimals && !text.contains('.') {
+            return Ok(Cow::Owned(text.replacen(',', ".", 1)));
+        }
+        Ok(text)
+    }
+
+    /// Reads the text of a string, up to the closing `end` tag.
+    fn read_string_text(&mut self, end: QName) -> Result<Cow<'de, str>> {
+        let text = self.reader.read_text(end)?;
+        check_string(&*self.reader, &text)?;
+        Ok(text)
+    }
+
+    fn is_nil(&self, name: QName) -> bool {
+        match self.reader.extension_name(name) {
+            Some(name) => name.as_ref() == b"nil",
+            None => name == QName(b"nil"),
+        }
+    }
+
+    /// Reads the text before the first tag inside the value. This is either
+    /// the whole value for strings without a "string" tag, or whitespace
+    /// which should be ignored.
+    fn leading_text(&mut self) -> Result<Cow<'de, str>> {
+        match self.text.take() {
+            Some(text) => Ok(text),
+            None => self.reader.read_leading_text(),
+        }
+    }
 }
 
-impl<'de, 'a, 'r> serde::Deserializer<'de> for Deserializer<'a, 'r> {
+impl<'de, 'a, R> serde::Deserializer<'de> for ValueDeserializer<'a, 'de, R>
+where
+    R: XmlRead<'de>,
+{
     type Error = Error;
 
     #[allow(clippy::cognitive_complexity)]
-    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_any<V>(mut self, visitor: V) -> Result<V::Value>
     where
         V: serde::de::Visitor<'de>,
     {
-        let ret = match self.reader.read_event() {
-            // If we got text, this is a String value. This is an edge case
-            // because it's valid to have a string value without the inner
-            // "string" tag.
-            Ok(Event::Text(e)) => visitor.visit_str::<Self::Error>(
-                e.unescape()
-                    .map_err(DecodingError::from)?.as_ref(),
-            )?,
-
-            // Alternatively, if we got the matching end tag, this is an empty
-            // string value. Note that we need to return early here so the end
-            // doesn't try to read the closing tag.
-            Ok(Event::End(ref e)) if e.name() == QName(b"value") => return visitor.visit_str(""),
-
-            Ok(Event::Start(ref e)) => match e.name() {
-                QName(b"int") | QName(b"i4") | QName(b"i8") => {
-                    let text = self
-                        .reader
-                        .read_text(e.name())
-                        .map_err(DecodingError::from)?;
-
-                    let val: i64 = text.parse().map_err(DecodingError::from)?;
-
-                    if let Ok(val) = val.try_into() {
-                        visitor.visit_i8::<Self::Error>(val)?
-                    } else if let Ok(val) = val.try_into() {
-                        visitor.visit_i16::<Self::Error>(val)?
-                    } else if let Ok(val) = val.try_into() {
-                        visitor.visit_i32::<Self::Error>(val)?
-                    } else {
-                        visitor.visit_i64::<Self::Error>(val)?
+        let text = self.leading_text()?;
+
+        let ret = match self.reader.read_event()? {
+            // If we got the matching end tag, the text is a String value. This
+            // is an edge case because it's valid to have a string value
+            // without the inner "string" tag. Note that we need to return
+            // early here so the end doesn't try to read the closing tag.
+            Event::End(ref e) if e.name() == QName(b"value") => {
+                check_string(&*self.reader, &text)?;
+                return visit_cow_str(visitor, text);
+            }
+
+            // Only whitespace is allowed between the value tag and the tag
+            // for the type.
+            Event::Start(ref e) if !is_whitespace(text.as_bytes()) => {
+                return Err(DecodingError::UnexpectedTag(
+                    String::from_utf8_lossy(e.name().into_inner()).into(),
+                    "/value".into(),
+                )
+                .into())
+            }
+
+            Event::Start(ref e) => match self.reader.extension_name(e.name()) {
+                Some(name) => match name.as_ref() {
+                    b"i1" => {
+                        let text = self.reader.read_trimmed_text(e.name())?;
+                        visitor.visit_i8::<Self::Error>(text.parse().map_err(DecodingError::from)?)?
                     }
-                }
+                    b"i2" => {
+                        let text = self.reader.read_trimmed_text(e.name())?;
+                        visitor.visit_i16::<Self::Error>(text.parse().map_err(DecodingError::from)?)?
+                    }
+                    b"i8" => {
+                        let text = self.reader.read_trimmed_text(e.name())?;
+                        visitor.visit_i64::<Self::Error>(text.parse().map_err(DecodingError::from)?)?
+                    }
+                    b"float" => {
+                        let text = self.read_float_text(e.name())?;
+                        visitor.visit_f32::<Self::Error>(text.parse().map_err(DecodingError::from)?)?
+                    }
+                    b"biginteger" | b"bigdecimal" => {
+                        let text = self.reader.read_trimmed_text(e.name())?;
+                        if !is_big_number(&text, name.as_ref() == b"bigdecimal") {
+                            return Err(DecodingError::InvalidNumber(text.into_owned()).into());
+                        }
+                        visit_cow_str(visitor, text)?
+                    }
+                    b"dateTime" => self.visit_datetime(e.name(), visitor)?,
+                    b"serializable" => self.visit_base64(e.name(), visitor)?,
+                    b"nil" => self.visit_nil(e.name(), visitor)?,
+                    _ => {
+                        return Err(DecodingError::UnexpectedTag(
+                            String::from_utf8_lossy(e.name().into_inner()).into(),
+                            "one of ex:i1|ex:i2|ex:i8|ex:float|ex:biginteger|ex:bigdecimal|ex:dateTime|ex:serializable|ex:nil"
+                                .into(),
+                        )
+                        .into())
+                    }
+                },
 
-                QName(b"boolean") => {
-                    let text = self
-                        .reader
-                        .read_text(e.name())
-                        .map_err(DecodingError::from)?;
-                    match text.as_ref() {
-                        "1" => visitor.visit_bool::<Self::Error>(true),
-                        "0" => visitor.visit_bool::<Self::Error>(false),
-                        _ => return Err(DecodingError::BooleanDecodeError(text.into_owned()).into()),
-                    }?
-                }
+                None => match e.name() {
+                    QName(b"int") | QName(b"i4") | QName(b"i8") => {
+                        let text = self.reader.read_trimmed_text(e.name())?;
 
-                QName(b"string") => {
-                    visitor.visit_str::<Self::Error>(
-                        self.reader
-                            .read_text(e.name())
-                            .map_err(DecodingError::from)?.as_ref(),
-                    )?
-                }
+                        let val: i64 = text.parse().map_err(DecodingError::from)?;
+                        if self.reader.options().validate && e.name() != QName(b"i8") && i32::try_from(val).is_err() {
+                            return Err(DecodingError::InvalidDocument(format!(
+                                "<{}> value {} does not fit in 32 bits",
+                                String::from_utf8_lossy(e.name().into_inner()),
+                                val
+                            ))
+                            .into());
+                        }
 
-                QName(b"double") => {
-                    let text = self
-                        .reader
-                        .read_text(e.name())
-                        .map_err(DecodingError::from)?;
-                    visitor.visit_f64::<Self::Error>(text.parse().map_err(DecodingError::from)?)?
-                }
+                        if let Ok(val) = val.try_into() {
+                            visitor.visit_i8::<Self::Error>(val)?
+                        } else if let Ok(val) = val.try_into() {
+                            visitor.visit_i16::<Self::Error>(val)?
+                        } else if let Ok(val) = val.try_into() {
+                            visitor.visit_i32::<Self::Error>(val)?
+                        } else {
+                            visitor.visit_i64::<Self::Error>(val)?
+                        }
+                    }
 
-                QName(b"dateTime.iso8601") => {
-                    visitor.visit_str::<Self::Error>(
-                        self.reader
-                            .read_text(e.name())
-                            .map_err(DecodingError::from)?.as_ref(),
-                    )?
-                }
+                    QName(b"boolean") => {
+                        let text = self.reader.read_trimmed_text(e.name())?;
+                        let words = self.reader.options().boolean_words;
+                        match text.as_ref() {
+                            "1" => visitor.visit_bool::<Self::Error>(true),
+                            "0" => visitor.visit_bool::<Self::Error>(false),
+                            t if words && t.eq_ignore_ascii_case("true") => visitor.visit_bool::<Self::Error>(true),
+                            t if words && t.eq_ignore_ascii_case("false") => visitor.visit_bool::<Self::Error>(false),
+                            _ => return Err(DecodingError::BooleanDecodeError(text.into_owned()).into()),
+                        }?
+                    }
 
-                QName(b"base64") => {
-                    let text = self
-                        .reader
-                        .read_text(e.name())
-                        .map_err(DecodingError::from)?;
-                    visitor.visit_byte_buf::<Self::Error>(
-                       BASE64_STANDARD.decode(text.as_ref()).map_err(DecodingError::from)?,
-                    )?
-                }
+                    QName(b"string") => visit_cow_str(visitor, self.read_string_text(e.name())?)?,
 
-                QName(b"struct") => visitor.visit_map(MapDeserializer::new(self.reader))?,
+                    QName(b"double") => {
+                        let text = self.read_float_text(e.name())?;
+                        if self.reader.options().validate && !is_plain_decimal(&text) {
+                            return Err(DecodingError::InvalidDocument(format!(
+                                "<double> value {:?} is not a plain decimal number",
+                                text
+                            ))
+                            .into());
+                        }
+                        visitor.visit_f64::<Self::Error>(text.parse().map_err(DecodingError::from)?)?
+                    }
 
-                QName(b"array") => {
-                    visitor.visit_seq(SeqDeserializer::new(self.reader, QName(b"data"), Some(QName(b"array")))?)?
-                }
+                    QName(b"dateTime.iso8601") => self.visit_datetime(e.name(), visitor)?,
 
-                QName(b"nil") => {
-                    self.reader
-                        .read_to_end(e.name())
-                        .map_err(DecodingError::from)?;
-                    visitor.visit_unit::<Self::Error>()?
-                }
+                    QName(b"base64") => self.visit_base64(e.name(), visitor)?,
 
-                _ => {
-                    return Err(DecodingError::UnexpectedTag(
-                        String::from_utf8_lossy(e.name().into_inner()).into(),
-                        "one of int|i4|i8|boolean|string|double|dateTime.iso8601|base64|struct|array|nil"
-                            .into(),
-                    )
-                    .into())
-                }
+                    QName(b"struct") => visitor.visit_map(MapDeserializer::new(self.reader))?,
+
+                    QName(b"array") => {
+                        let mut seq = SeqDeserializer::new(self.reader, QName(b"data"), Some(QName(b"array")))?;
+                        let ret = visitor.visit_seq(&mut seq)?;
+                        seq.finish()?;
+                        ret
+                    }
+
+                    QName(b"nil") => self.visit_nil(e.name(), visitor)?,
+
+                    _ => {
+                        return Err(DecodingError::UnexpectedTag(
+                            String::from_utf8_lossy(e.name().into_inner()).into(),
+                            "one of int|i4|i8|boolean|string|double|dateTime.iso8601|base64|struct|array|nil"
+                                .into(),
+                        )
+                        .into())
+                    }
+                },
             },
 
             // Possible error states
-            Ok(Event::Eof) => {
+            Event::Eof => {
                 return Err(DecodingError::UnexpectedEOF(
                     "one of int|i4|i8|boolean|string|double|dateTime.iso8601|base64|struct|array|nil"
                         .into(),
@@ -138,134 +672,371 @@ impl<'de, 'a, 'r> serde::Deserializer<'de> for Deserializer<'a, 'r> {
                 .into())
             }
 
-            Ok(_) => {
+            _ => {
                 return Err(DecodingError::UnexpectedEvent(
                     "one of int|i4|i8|boolean|string|double|dateTime.iso8601|base64|struct|array|nil"
                         .into(),
                 )
                 .into())
             }
-
-            Err(e) => return Err(DecodingError::from(e).into()),
         };
 
-        self.reader
-            .read_to_end(QName(b"value"))
-            .map_err(DecodingError::from)?;
+        self.reader.read_to_end(QName(b"value"))?;
 
         Ok(ret)
     }
 
-    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_option<V>(mut self, visitor: V) -> Result<V::Value>
     where
         V: serde::de::Visitor<'de>,
     {
-        // Clone the reader and walk through it - if we get starting nil tag, we
-        // read to the end of the nil tag, replace the inner reader, and call
-        // visit_none. Otherwise, we defer to visitor.visit_some(self).
-        //
-        // We clone the reader (rather than using self.reader directly) as a way
-        // to "peek" at the next event.
-        let mut reader = self.reader.clone();
-
-        if let Ok(Event::Start(ref e)) = reader.read_event() {
-            if e.name() == QName(b"nil") {
-                reader.read_to_end(e.name()).map_err(DecodingError::from)?;
-                *self.reader = reader;
+        // Peek at the next event after any whitespace - if we get starting nil
+        // tag, we read to the end of the enclosing value and call visit_none.
+        // Otherwise, we defer to visitor.visit_some(self).
+        let text = self.leading_text()?;
+        if let Event::Start(e) = self.reader.peek_event()? {
+            let e = e.to_owned();
+            if self.is_nil(e.name()) && is_whitespace(text.as_bytes()) {
+                self.reader.read_event()?;
+                self.reader.read_to_end(e.name())?;
+                self.reader.read_to_end(QName(b"value"))?;
                 return visitor.visit_none::<Self::Error>();
             }
         }
 
+        self.text = Some(text);
         visitor.visit_some(self)
     }
 
+    fn deserialize_newtype_struct<V>(mut self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        if name == datetime::VALUE_TOKEN {
+            self.mark_datetime = true;
+            return self.deserialize_any(visitor);
+        }
+
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        mut self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // Unit variants are written as a string and data variants as a
+        // struct with a single member named after the variant. Anything else
+        // is passed on as is so the visitor can raise an error.
+        let text = self.leading_text()?;
+        let is_string = match self.reader.peek_event()? {
+            Event::End(ref e) if e.name() == QName(b"value") => true,
+            Event::Start(ref e) if is_whitespace(text.as_bytes()) => match e.name() {
+                QName(b"string") => true,
+                QName(b"struct") => {
+                    self.reader.read_event()?;
+                    let ret = visitor.visit_enum(EnumDeserializer::new(self.reader))?;
+                    self.reader.read_to_end(QName(b"value"))?;
+                    return Ok(ret);
+                }
+                _ => false,
+            },
+            _ => false,
+        };
+
+        if !is_string {
+            self.text = Some(text);
+            return self.deserialize_any(visitor);
+        }
+
+        let variant = match self.reader.read_event()? {
+            Event::Start(ref e) => {
+                let text = self.read_string_text(e.name())?;
+                self.reader.read_to_end(QName(b"value"))?;
+                text
+            }
+            _ => {
+                check_string(&*self.reader, &text)?;
+                text
+            }
+        };
+        visitor.visit_enum(variant.into_deserializer())
+    }
+
     forward_to_deserialize_any!(
         bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
-        byte_buf unit unit_struct newtype_struct seq tuple
+        byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    );
+}
+
+/// Deserializes the contents of a `<params>` tag as a sequence, with each
+/// `<param>` being a separate element. This is supposed to be used after the
+/// `<params>` start tag was read.
+pub(crate) struct ParamsDeserializer<'a, R> {
+    reader: &'a mut R,
+    empty: bool,
+}
+
+impl<'a, R> ParamsDeserializer<'a, R> {
+    pub(crate) fn new(reader: &'a mut R) -> Self {
+        ParamsDeserializer {
+            reader,
+            empty: false,
+        }
+    }
+
+    /// Creates a deserializer for a request without a `<params>` element,
+    /// which has no params at all.
+    pub(crate) fn empty(reader: &'a mut R) -> Self {
+        ParamsDeserializer {
+            reader,
+            empty: true,
+        }
+    }
+}
+
+impl<'de, 'a, R> serde::Deserializer<'de> for ParamsDeserializer<'a, R>
+where
+    R: XmlRead<'de>,
+{
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let mut seq = ParamsSeqDeserializer {
+            reader: self.reader,
+            count: 0,
+            done: self.empty,
+        };
+        let ret = visitor.visit_seq(&mut seq)?;
+
+        // Make sure the visitor consumed every param, otherwise they would be
+        // silently dropped.
+        if !seq.done {
+            match seq.reader.read_tag_event()? {
+                Event::End(ref e) if e.name() == QName(b"params") => {}
+                _ => {
+                    return Err(serde::de::Error::invalid_length(
+                        seq.count + 1,
+                        &format!("{} params", seq.count).as_str(),
+                    ))
+                }
+            }
+        }
+
+        Ok(ret)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // An empty list of params can be treated as a unit value.
+        if self.empty {
+            return visitor.visit_unit();
+        }
+        match self.reader.read_tag_event()? {
+            Event::End(ref e) if e.name() == QName(b"params") => visitor.visit_unit(),
+            _ => Err(serde::de::Error::invalid_length(1, &"no params")),
+        }
+    }
+
+    forward_to_deserialize_any!(
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
+        byte_buf option unit_struct newtype_struct seq tuple
         tuple_struct map struct enum identifier ignored_any
     );
 }
-struct SeqDeserializer<'a, 'r> {
-    reader: &'a mut Reader<&'r [u8]>,
+
+struct ParamsSeqDeserializer<'a, R> {
+    reader: &'a mut R,
+    count: usize,
+    done: bool,
+}
+
+impl<'de, 'a, R> serde::de::SeqAccess<'de> for ParamsSeqDeserializer<'a, R>
+where
+    R: XmlRead<'de>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        if self.done {
+            return Ok(None);
+        }
+
+        match self.reader.read_tag_event()? {
+            Event::End(ref e) if e.name() == QName(b"params") => {
+                self.done = true;
+                Ok(None)
+            }
+            Event::Start(ref e) if e.name() == QName(b"param") => {
+                let max = self.reader.options().limits.max_params;
+                check_limit("params", self.count + 1, max)?;
+                self.reader.expect_tag(QName(b"value"))?;
+                let ret = seed.deserialize(ValueDeserializer::new(&mut *self.reader)?)?;
+                self.reader.read_to_end(QName(b"param"))?;
+                self.count += 1;
+                Ok(Some(ret))
+            }
+            _ => Err(DecodingError::UnexpectedEvent("one of param".to_string()).into()),
+        }
+    }
+}
+
+struct SeqDeserializer<'a, R> {
+    reader: &'a mut R,
     end: QName<'a>,
     end_maybe: Option<QName<'a>>,
+    count: usize,
+    done: bool,
 }
 
-impl<'a, 'r> SeqDeserializer<'a, 'r> {
-    fn new(
-        reader: &'a mut Reader<&'r [u8]>,
-        end: QName<'a>,
-        end_maybe: Option<QName<'a>>,
-    ) -> Result<Self> {
-        let ret = SeqDeserializer {
+impl<'de, 'a, R> SeqDeserializer<'a, R>
+where
+    R: XmlRead<'de>,
+{
+    fn new(reader: &'a mut R, end: QName<'a>, end_maybe: Option<QName<'a>>) -> Result<Self> {
+        match reader.read_tag_event()? {
+            Event::Start(ref e) if e.name() == end => {}
+            e => return Err(unexpected(&e, end_maybe.unwrap_or(end))),
+        }
+
+        Ok(SeqDeserializer {
             reader,
             end,
             end_maybe,
-        };
+            count: 0,
+            done: false,
+        })
+    }
 
-        ret.reader.expect_tag(ret.end)?;
+    /// Makes sure the visitor consumed every element when validating.
+    /// Otherwise any remaining elements are skipped along with the rest of
+    /// the value.
+    fn finish(self) -> Result<()> {
+        if self.done || !self.reader.options().validate {
+            return Ok(());
+        }
 
-        Ok(ret)
+        match self.reader.read_tag_event()? {
+            Event::End(ref e) if e.name() == self.end => match self.end_maybe {
+                Some(end) => self.reader.read_to_end(end),
+                None => Ok(()),
+            },
+            _ => Err(serde::de::Error::invalid_length(
+                self.count + 1,
+                &format!("{} elements", self.count).as_str(),
+            )),
+        }
     }
 }
 
-impl<'de, 'a, 'r> serde::de::SeqAccess<'de> for SeqDeserializer<'a, 'r> {
+impl<'de, 'a, R> serde::de::SeqAccess<'de> for SeqDeserializer<'a, R>
+where
+    R: XmlRead<'de>,
+{
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
     where
         T: serde::de::DeserializeSeed<'de>,
     {
-        match self.reader.read_event() {
-            Ok(Event::End(ref e)) if e.name() == self.end => {
+        match self.reader.read_tag_event()? {
+            Event::End(ref e) if e.name() == self.end => {
                 if let Some(end) = self.end_maybe {
-                    self.reader.read_to_end(end).map_err(DecodingError::from)?;
+                    self.reader.read_to_end(end)?;
                 }
+                self.done = true;
                 Ok(None)
             }
-            Ok(Event::Start(ref e)) if e.name() == QName(b"value") => {
-                Ok(Some(seed.deserialize(Deserializer::new(self.reader)?)?))
+            Event::Start(ref e) if e.name() == QName(b"value") => {
+                let max = self.reader.options().limits.max_elements;
+                check_limit("array elements", self.count + 1, max)?;
+                let ret = seed.deserialize(ValueDeserializer::new(&mut *self.reader)?)?;
+                self.count += 1;
+                Ok(Some(ret))
             }
-            Ok(_) => Err(DecodingError::UnexpectedEvent("one of value".to_string()).into()),
-            Err(e) => Err(DecodingError::from(e).into()),
+            e => Err(unexpected(&e, self.end)),
         }
     }
 }
 
-struct MapDeserializer<'a, 'r> {
-    reader: &'a mut Reader<&'r [u8]>,
+struct MapDeserializer<'a, R> {
+    reader: &'a mut R,
+    count: usize,
+    // The value of the current member, if it came before the name.
+    value: Option<Value>,
 }
 
-impl<'a, 'r> MapDeserializer<'a, 'r> {
-    fn new(reader: &'a mut Reader<&'r [u8]>) -> Self {
-        MapDeserializer { reader }
+impl<'a, R> MapDeserializer<'a, R> {
+    fn new(reader: &'a mut R) -> Self {
+        MapDeserializer {
+            reader,
+            count: 0,
+            value: None,
+        }
     }
 }
 
-impl<'de, 'a, 'r> serde::de::MapAccess<'de> for MapDeserializer<'a, 'r> {
+impl<'de, 'a, R> serde::de::MapAccess<'de> for MapDeserializer<'a, R>
+where
+    R: XmlRead<'de>,
+{
     type Error = Error;
 
     fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
     where
         T: serde::de::DeserializeSeed<'de>,
     {
-        match self.reader.read_event() {
+        match self.reader.read_tag_event()? {
             // The base case is that we found a closing tag for the tag we were
             // looking for.
-            Ok(Event::End(ref e)) if e.name() == QName(b"struct") => Ok(None),
+            Event::End(ref e) if e.name() == QName(b"struct") => Ok(None),
 
             // If we got a member start tag, we know there's a key and value
             // coming.
-            Ok(Event::Start(ref e)) if e.name() == QName(b"member") => {
-                self.reader.expect_tag(QName(b"name"))?;
+            Event::Start(ref e) if e.name() == QName(b"member") => {
+                self.count += 1;
+                let max = self.reader.options().limits.max_members;
+                check_limit("struct members", self.count, max)?;
+
+                match self.reader.read_tag_event()? {
+                    Event::Start(ref e) if e.name() == QName(b"name") => {}
+
+                    // Some servers put the name last, in which case the value
+                    // is held on to until it's asked for.
+                    Event::Start(ref e)
+                        if e.name() == QName(b"value")
+                            && self.reader.options().name_after_value =>
+                    {
+                        let value = serde::Deserialize::deserialize(ValueDeserializer::new(
+                            &mut *self.reader,
+                        )?)?;
+                        self.reader.expect_tag(QName(b"name"))?;
+                        self.value = Some(value);
+                    }
+
+                    e => return Err(unexpected(&e, QName(b"member"))),
+                }
+
                 Ok(Some(
-                    seed.deserialize(MapKeyDeserializer::new(self.reader))?,
+                    seed.deserialize(MapKeyDeserializer::new(&mut *self.reader))?,
                 ))
             }
 
-            // Any other event or error is unexpected and is an actual error.
-            Ok(e) => Err(DecodingError::UnexpectedEvent(format!("map key read: {:?}", e)).into()),
-            Err(e) => Err(DecodingError::from(e).into()),
+            // Any other event is unexpected and is an actual error.
+            e => Err(unexpected(&e, QName(b"struct"))),
         }
     }
 
@@ -273,50 +1044,216 @@ impl<'de, 'a, 'r> serde::de::MapAccess<'de> for MapDeserializer<'a, 'r> {
     where
         T: serde::de::DeserializeSeed<'de>,
     {
-        let ret = match self.reader.read_event() {
-            Ok(Event::Start(ref e)) if e.name() == QName(b"value") => {
-                Ok(seed.deserialize(Deserializer::new(self.reader)?)?)
+        if let Some(value) = self.value.take() {
+            let ret = seed.deserialize(value)?;
+            self.reader.read_to_end(QName(b"member"))?;
+            return Ok(ret);
+        }
+
+        let ret = match self.reader.read_tag_event()? {
+            Event::Start(ref e) if e.name() == QName(b"value") => {
+                seed.deserialize(ValueDeserializer::new(&mut *self.reader)?)?
             }
-            Ok(e) => Err(DecodingError::UnexpectedEvent(format!("map value read: {:?}", e)).into()),
-            Err(e) => Err(DecodingError::from(e).into()),
+            e => return Err(unexpected(&e, QName(b"member"))),
         };
 
-        self.reader
-            .read_to_end(QName(b"member"))
-            .map_err(DecodingError::from)?;
+        self.reader.read_to_end(QName(b"member"))?;
 
-        ret
+        Ok(ret)
     }
 }
 
-struct MapKeyDeserializer<'a, 'r> {
-    reader: &'a mut Reader<&'r [u8]>,
+struct MapKeyDeserializer<'a, R> {
+    reader: &'a mut R,
 }
 
-impl<'a, 'r> MapKeyDeserializer<'a, 'r> {
-    fn new(reader: &'a mut Reader<&'r [u8]>) -> Self {
+impl<'a, R> MapKeyDeserializer<'a, R> {
+    fn new(reader: &'a mut R) -> Self {
         MapKeyDeserializer { reader }
     }
 }
 
-impl<'de, 'a, 'r> serde::Deserializer<'de> for MapKeyDeserializer<'a, 'r> {
+impl<'de, 'a, R> serde::Deserializer<'de> for MapKeyDeserializer<'a, R>
+where
+    R: XmlRead<'de>,
+{
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: serde::de::Visitor<'de>,
     {
-        visitor.visit_string(
-            self.reader
-                .read_text(QName(b"name"))
-                .map_err(DecodingError::from)?
-                .into(),
-        )
+        let text = self.reader.read_text(QName(b"name"))?;
+        check_string(&*self.reader, &text)?;
+        visit_cow_str(visitor, text)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let text = self.reader.read_text(QName(b"name"))?;
+        check_string(&*self.reader, &text)?;
+        visitor.visit_enum(text.into_deserializer())
     }
 
     forward_to_deserialize_any!(
         bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
         byte_buf unit unit_struct newtype_struct seq tuple
-        tuple_struct map struct enum identifier ignored_any option
+        tuple_struct map struct identifier ignored_any option
     );
 }
+
+/// Deserializes a data variant from the contents of a `<struct>` tag, which
+/// should contain a single member named after the variant. This is supposed
+/// to be used after the `<struct>` start tag was read.
+struct EnumDeserializer<'a, R> {
+    reader: &'a mut R,
+}
+
+impl<'a, R> EnumDeserializer<'a, R> {
+    fn new(reader: &'a mut R) -> Self {
+        EnumDeserializer { reader }
+    }
+}
+
+impl<'de, 'a, R> serde::de::EnumAccess<'de> for EnumDeserializer<'a, R>
+where
+    R: XmlRead<'de>,
+{
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<T>(self, seed: T) -> Result<(T::Value, Self)>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        self.reader.expect_tag(QName(b"member"))?;
+        self.reader.expect_tag(QName(b"name"))?;
+        let variant = seed.deserialize(MapKeyDeserializer::new(&mut *self.reader))?;
+        self.reader.expect_tag(QName(b"value"))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a, R> EnumDeserializer<'a, R>
+where
+    R: XmlRead<'de>,
+{
+    /// Deserializes the value of the variant, then makes sure it was the only
+    /// member of the struct.
+    fn variant_value<F, T>(self, f: F) -> Result<T>
+    where
+        F: FnOnce(ValueDeserializer<'_, 'de, R>) -> Result<T>,
+    {
+        let ret = f(ValueDeserializer::new(&mut *self.reader)?)?;
+        self.reader.read_to_end(QName(b"member"))?;
+        match self.reader.read_tag_event()? {
+            Event::End(ref e) if e.name() == QName(b"struct") => Ok(ret),
+            _ => Err(DecodingError::UnexpectedEvent("/struct".to_string()).into()),
+        }
+    }
+}
+
+impl<'de, 'a, R> serde::de::VariantAccess<'de> for EnumDeserializer<'a, R>
+where
+    R: XmlRead<'de>,
+{
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        self.variant_value(|de| serde::Deserialize::deserialize(de))
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        self.variant_value(|de| seed.deserialize(de))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.variant_value(|de| serde::Deserializer::deserialize_seq(de, visitor))
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.variant_value(|de| serde::Deserializer::deserialize_map(de, visitor))
+    }
+}
+
+/// Checks the text of an `ex:biginteger` or `ex:bigdecimal` value, which
+/// should be formatted the same as Java's `toString` output.
+fn is_big_number(text: &str, decimal: bool) -> bool {
+    fn is_integer(text: &str) -> bool {
+        let digits = text
+            .strip_prefix('-')
+            .or_else(|| text.strip_prefix('+'))
+            .unwrap_or(text);
+        !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+    }
+
+    if !decimal {
+        return is_integer(text);
+    }
+
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(idx) => (&text[..idx], Some(&text[idx + 1..])),
+        None => (text, None),
+    };
+    let mut parts = mantissa.splitn(2, '.');
+    let whole = parts.next().unwrap_or_default();
+    let valid_fraction = match parts.next() {
+        Some(fraction) => fraction.bytes().all(|b| b.is_ascii_digit()),
+        None => true,
+    };
+
+    is_integer(whole) && valid_fraction && exponent.is_none_or(is_integer)
+}
+
+/// Makes sure a string is within the configured length limit.
+pub(crate) fn check_string<'de, R>(reader: &R, text: &str) -> Result<()>
+where
+    R: XmlRead<'de> + ?Sized,
+{
+    let max = reader.options().limits.max_string_bytes;
+    check_limit("bytes in a string", text.len(), max)
+}
+
+/// Passes a string on to the visitor, borrowing from the input where possible.
+/// Text can only be borrowed if it didn't contain any escaped characters.
+fn visit_cow_str<'de, V>(visitor: V, text: Cow<'de, str>) -> Result<V::Value>
+where
+    V: serde::de::Visitor<'de>,
+{
+    match text {
+        Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+        Cow::Owned(s) => visitor.visit_string(s),
+    }
+}
+
+/// Checks that a double is written the way the spec describes: an optional
+/// sign, then digits with an optional decimal point.
+fn is_plain_decimal(text: &str) -> bool {
+    let digits = text
+        .strip_prefix('-')
+        .or_else(|| text.strip_prefix('+'))
+        .unwrap_or(text);
+    let mut parts = digits.splitn(2, '.');
+    let whole = parts.next().unwrap_or_default();
+    let fraction = parts.next().unwrap_or_default();
+
+    !(whole.is_empty() && fraction.is_empty())
+        && whole.bytes().all(|b| b.is_ascii_digit())
+        && fraction.bytes().all(|b| b.is_ascii_digit())
+}
@@ -0,0 +1,135 @@
+//! Encoding of output in character encodings other than UTF-8.
+
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::str;
+
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::error::EncodingError;
+use crate::Result;
+
+/// A character encoding requests and responses can be written in.
+#[derive(Clone, Copy)]
+pub(crate) enum OutputEncoding {
+    Utf8,
+    // The encoding standard treats ISO-8859-1 as an alias of Windows-1252,
+    // which maps 0x80 to 0x9F to printable characters, so it's handled
+    // separately.
+    Latin1,
+    Other(&'static Encoding),
+}
+
+impl OutputEncoding {
+    pub(crate) fn for_label(label: &str) -> Result<Self> {
+        const LATIN1_LABELS: &[&str] = &["iso-8859-1", "iso8859-1", "iso_8859-1", "latin1", "l1"];
+        if LATIN1_LABELS
+            .iter()
+            .any(|known| known.eq_ignore_ascii_case(label.trim()))
+        {
+            return Ok(OutputEncoding::Latin1);
+        }
+
+        match Encoding::for_label(label.as_bytes()) {
+            Some(encoding) if encoding == UTF_8 => Ok(OutputEncoding::Utf8),
+            // Encodings which can't be encoded to, like UTF-16, have another
+            // output encoding.
+            Some(encoding) if encoding.output_encoding() == encoding => {
+                Ok(OutputEncoding::Other(encoding))
+            }
+            _ => Err(EncodingError::UnsupportedEncoding(label.into()).into()),
+        }
+    }
+
+    /// Appends the text in this encoding, with numeric character references
+    /// for characters which can't be represented.
+    fn encode(self, text: &str, out: &mut Vec<u8>) {
+        match self {
+            OutputEncoding::Utf8 => out.extend_from_slice(text.as_bytes()),
+            OutputEncoding::Latin1 => {
+                for c in text.chars() {
+                    match u8::try_from(u32::from(c)) {
+                        Ok(b) => out.push(b),
+                        Err(_) => out.extend_from_slice(format!("&#{};", u32::from(c)).as_bytes()),
+                    }
+                }
+            }
+            OutputEncoding::Other(encoding) => {
+                let (bytes, _, _) = encoding.encode(text);
+                out.extend_from_slice(&bytes);
+            }
+        }
+    }
+}
+
+/// An `io::Write` adapter which encodes the UTF-8 written to it.
+pub(crate) struct EncodingWriter<W> {
+    inner: W,
+    encoding: OutputEncoding,
+    // The start of a character which was split across writes.
+    pending: Vec<u8>,
+    buf: Vec<u8>,
+}
+
+impl<W> EncodingWriter<W>
+where
+    W: Write,
+{
+    pub(crate) fn new(inner: W, encoding: OutputEncoding) -> Self {
+        EncodingWriter {
+            inner,
+            encoding,
+            pending: Vec::new(),
+            buf: Vec::new(),
+        }
+    }
+}
+
+impl<W> Write for EncodingWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if let OutputEncoding::Utf8 = self.encoding {
+            return self.inner.write(data);
+        }
+
+        self.pending.extend_from_slice(data);
+        let valid_up_to = match str::from_utf8(&self.pending) {
+            Ok(text) => text.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        };
+        let text = str::from_utf8(&self.pending[..valid_up_to])
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        self.buf.clear();
+        self.encoding.encode(text, &mut self.buf);
+        self.inner.write_all(&self.buf)?;
+        self.pending.drain(..valid_up_to);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_characters() {
+        let mut out = Vec::new();
+        let mut writer = EncodingWriter::new(&mut out, OutputEncoding::Latin1);
+        let text = "\u{e9}\u{20ac}".as_bytes();
+        for b in text {
+            assert_eq!(writer.write(&[*b]).unwrap(), 1);
+        }
+        assert_eq!(out, b"\xe9&#8364;");
+
+        let mut writer = EncodingWriter::new(Vec::new(), OutputEncoding::Latin1);
+        assert!(writer.write(b"\xff").is_err());
+    }
+}
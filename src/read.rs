@@ -0,0 +1,472 @@
+use std::borrow::Cow;
+use std::io::BufRead;
+
+use quick_xml::{
+    encoding::Decoder,
+    escape::escape,
+    events::{BytesEnd, BytesText, Event},
+    name::PrefixDeclaration,
+    Reader,
+};
+
+use crate::de::{check_limit, DecoderOptions};
+use crate::error::DecodingError;
+use crate::Result;
+
+/// A source of XML events which the [Deserializer](crate::Deserializer) can
+/// pull from.
+///
+/// This abstracts over borrowing events straight out of an in-memory slice
+/// ([SliceReader]) and reading owned events from a buffered `io` source
+/// ([IoReader]). This trait is sealed and cannot be implemented outside of
+/// this crate.
+pub trait XmlRead<'de>: private::Sealed {
+    /// Returns the next event, consuming it.
+    fn read_event(&mut self) -> Result<Event<'de>>;
+
+    /// Returns the next event without consuming it.
+    fn peek_event(&mut self) -> Result<&Event<'de>>;
+
+    /// Returns the options this source was created with.
+    #[doc(hidden)]
+    fn options(&self) -> &DecoderOptions;
+
+    /// Returns the namespace URI bound to a prefix (or the default namespace
+    /// for an empty prefix) in the current element. Namespaces are only
+    /// tracked when extensions are enabled.
+    #[doc(hidden)]
+    fn resolve_namespace(&self, prefix: &[u8]) -> Option<&[u8]>;
+}
+
+impl<'de, R> XmlRead<'de> for &mut R
+where
+    R: XmlRead<'de> + ?Sized,
+{
+    fn read_event(&mut self) -> Result<Event<'de>> {
+        (**self).read_event()
+    }
+
+    fn peek_event(&mut self) -> Result<&Event<'de>> {
+        (**self).peek_event()
+    }
+
+    fn options(&self) -> &DecoderOptions {
+        (**self).options()
+    }
+
+    fn resolve_namespace(&self, prefix: &[u8]) -> Option<&[u8]> {
+        (**self).resolve_namespace(prefix)
+    }
+}
+
+fn configure<R>(reader: &mut Reader<R>) {
+    reader.expand_empty_elements(true);
+}
+
+/// Makes sure the encoding detected from a BOM or the XML declaration can be
+/// decoded. Only encodings which are a superset of ASCII are supported, as
+/// markup is parsed byte by byte.
+fn check_encoding<R>(reader: &Reader<R>) -> Result<()> {
+    let encoding = reader.decoder().encoding();
+    if !encoding.is_ascii_compatible() {
+        return Err(DecodingError::UnsupportedEncoding(encoding.name().into()).into());
+    }
+    Ok(())
+}
+
+/// Turns a CDATA section into the equivalent text, so it is read as part of
+/// the surrounding text content.
+fn cdata_to_text(event: Event, decoder: Decoder) -> Result<Event> {
+    let cdata = match event {
+        Event::CData(e) => e,
+        e => return Ok(e),
+    };
+    let text = match cdata.into_inner() {
+        Cow::Borrowed(bytes) => decoder.decode(bytes),
+        Cow::Owned(bytes) => decoder
+            .decode(&bytes)
+            .map(|text| Cow::Owned(text.into_owned())),
+    }
+    .map_err(DecodingError::from)?;
+    // The text is escaped again as it will be unescaped when it is read, but
+    // it can still be borrowed if there was nothing to escape.
+    let escaped = match escape(&text) {
+        Cow::Borrowed(_) => None,
+        Cow::Owned(escaped) => Some(escaped),
+    };
+    Ok(Event::Text(BytesText::from_escaped(
+        escaped.map_or(text, Cow::Owned),
+    )))
+}
+
+/// Every element name used by XML-RPC, in its canonical case.
+const ELEMENT_NAMES: &[&str] = &[
+    "methodCall",
+    "methodName",
+    "methodResponse",
+    "params",
+    "param",
+    "fault",
+    "value",
+    "i4",
+    "int",
+    "i8",
+    "boolean",
+    "string",
+    "double",
+    "dateTime.iso8601",
+    "base64",
+    "nil",
+    "struct",
+    "member",
+    "name",
+    "array",
+    "data",
+];
+
+/// Renames XML-RPC elements written in a different case, such as `<VALUE>`,
+/// to their canonical names. Prefixed names are never touched.
+fn normalize_case(event: Event) -> Event {
+    fn canonical(name: &[u8]) -> Option<&'static str> {
+        ELEMENT_NAMES
+            .iter()
+            .find(|known| known.as_bytes() != name && known.as_bytes().eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    match event {
+        Event::Start(mut e) => {
+            if let Some(name) = canonical(e.name().into_inner()) {
+                e.set_name(name.as_bytes());
+            }
+            Event::Start(e)
+        }
+        Event::End(e) => match canonical(e.name().into_inner()) {
+            Some(name) => Event::End(BytesEnd::new(name)),
+            None => Event::End(e),
+        },
+        e => e,
+    }
+}
+
+/// Checks the parts of the XML-RPC grammar which can be checked one event
+/// at a time. `started` is whether any event came before this one.
+fn validate(event: &Event, started: bool) -> Result<()> {
+    let problem = match event {
+        Event::Decl(_) if started => {
+            "the XML declaration must be at the start of the document".into()
+        }
+        Event::Start(e) => {
+            let mut problem = None;
+            for attr in e.attributes() {
+                let attr = attr
+                    .map_err(quick_xml::Error::from)
+                    .map_err(DecodingError::from)?;
+                if attr.key.as_namespace_binding().is_none() {
+                    problem = Some(format!(
+                        "unexpected attribute {} on <{}>",
+                        String::from_utf8_lossy(attr.key.into_inner()),
+                        String::from_utf8_lossy(e.name().into_inner()),
+                    ));
+                    break;
+                }
+            }
+            match problem {
+                Some(problem) => problem,
+                None => return Ok(()),
+            }
+        }
+        _ => return Ok(()),
+    };
+    Err(DecodingError::InvalidDocument(problem).into())
+}
+
+/// The state shared by both readers which is updated as events are read.
+#[derive(Clone)]
+struct State {
+    options: DecoderOptions,
+    namespaces: Namespaces,
+    // Whether any event was read yet.
+    started: bool,
+    // The number of arrays and structs which are currently open.
+    depth: usize,
+}
+
+impl State {
+    fn new(options: DecoderOptions) -> Self {
+        State {
+            options,
+            namespaces: Namespaces::default(),
+            started: false,
+            depth: 0,
+        }
+    }
+
+    /// Applies the options which affect every event as it is read. Returns
+    /// `None` for events which should be skipped, which are comments,
+    /// processing instructions and allowed document type declarations.
+    fn process<'e>(&mut self, event: Event<'e>) -> Result<Option<Event<'e>>> {
+        if let Event::Comment(_) | Event::PI(_) = event {
+            self.started = true;
+            return Ok(None);
+        }
+        if let Event::DocType(_) = event {
+            if !self.options.allow_doctype {
+                return Err(DecodingError::InvalidDocument(
+                    "document type declarations are not allowed".into(),
+                )
+                .into());
+            }
+            self.started = true;
+            return Ok(None);
+        }
+
+        let event = if self.options.ignore_tag_case {
+            normalize_case(event)
+        } else {
+            event
+        };
+        if self.options.validate {
+            validate(&event, self.started)?;
+        }
+        if self.options.extensions {
+            self.namespaces.update(&event)?;
+        }
+        self.track_depth(&event)?;
+        self.started = true;
+        Ok(Some(event))
+    }
+
+    /// Keeps count of the open arrays and structs. As each of them is
+    /// decoded recursively, the depth is limited before the deserializer
+    /// gets to see any nested value.
+    fn track_depth(&mut self, event: &Event) -> Result<()> {
+        match event {
+            Event::Start(e) if matches!(e.name().as_ref(), b"array" | b"struct") => {
+                self.depth += 1;
+                check_limit(
+                    "levels of nested arrays and structs",
+                    self.depth,
+                    self.options.limits.max_depth,
+                )
+            }
+            Event::End(e) if matches!(e.name().as_ref(), b"array" | b"struct") => {
+                self.depth = self.depth.saturating_sub(1);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Tracks the namespace prefixes declared by the currently open elements.
+#[derive(Clone, Default)]
+struct Namespaces {
+    depth: usize,
+    // The depth of the element each binding was declared on, the prefix
+    // (empty for the default namespace) and the URI.
+    bindings: Vec<(usize, Vec<u8>, Vec<u8>)>,
+}
+
+impl Namespaces {
+    fn update(&mut self, event: &Event) -> Result<()> {
+        match event {
+            Event::Start(e) => {
+                self.depth += 1;
+                for attr in e.attributes() {
+                    let attr = attr
+                        .map_err(quick_xml::Error::from)
+                        .map_err(DecodingError::from)?;
+                    let prefix = match attr.key.as_namespace_binding() {
+                        Some(PrefixDeclaration::Default) => Vec::new(),
+                        Some(PrefixDeclaration::Named(prefix)) => prefix.to_vec(),
+                        None => continue,
+                    };
+                    self.bindings
+                        .push((self.depth, prefix, attr.value.into_owned()));
+                }
+            }
+            Event::End(_) => {
+                while matches!(self.bindings.last(), Some((depth, _, _)) if *depth == self.depth) {
+                    self.bindings.pop();
+                }
+                self.depth = self.depth.saturating_sub(1);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn resolve(&self, prefix: &[u8]) -> Option<&[u8]> {
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(_, p, _)| p == prefix)
+            .map(|(_, _, uri)| uri.as_slice())
+    }
+}
+
+/// An [XmlRead] implementation which borrows events from an in-memory slice,
+/// allowing strings to be deserialized without copying them.
+#[derive(Clone)]
+pub struct SliceReader<'de> {
+    reader: Reader<&'de [u8]>,
+    peeked: Option<Event<'de>>,
+    state: State,
+}
+
+impl<'de> SliceReader<'de> {
+    /// Wraps a quick-xml reader over a slice. Note that the reader will be
+    /// configured to expand empty elements.
+    pub fn new(reader: Reader<&'de [u8]>) -> Self {
+        SliceReader::with_options(reader, DecoderOptions::default())
+    }
+
+    /// Wraps a quick-xml reader over a slice, decoding with the given
+    /// options.
+    pub fn with_options(mut reader: Reader<&'de [u8]>, options: DecoderOptions) -> Self {
+        configure(&mut reader);
+        SliceReader {
+            reader,
+            peeked: None,
+            state: State::new(options),
+        }
+    }
+
+    /// Creates a reader which borrows from a string.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &'de str) -> Self {
+        SliceReader::new(Reader::from_str(input))
+    }
+
+    /// Creates a reader which borrows from raw bytes. The encoding is taken
+    /// from a byte order mark or the XML declaration, and defaults to UTF-8.
+    pub fn from_slice(input: &'de [u8]) -> Self {
+        SliceReader::new(Reader::from_reader(input))
+    }
+
+    /// Unwraps the underlying quick-xml reader.
+    pub fn into_inner(self) -> Reader<&'de [u8]> {
+        self.reader
+    }
+}
+
+impl<'de> XmlRead<'de> for SliceReader<'de> {
+    fn read_event(&mut self) -> Result<Event<'de>> {
+        if let Some(event) = self.peeked.take() {
+            return Ok(event);
+        }
+
+        loop {
+            let event = self.reader.read_event().map_err(DecodingError::from)?;
+            if !self.state.started || matches!(event, Event::Decl(_)) {
+                check_encoding(&self.reader)?;
+            }
+            let event = cdata_to_text(event, self.reader.decoder())?;
+            if let Some(event) = self.state.process(event)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    fn peek_event(&mut self) -> Result<&Event<'de>> {
+        let event = self.read_event()?;
+        Ok(self.peeked.insert(event))
+    }
+
+    fn options(&self) -> &DecoderOptions {
+        &self.state.options
+    }
+
+    fn resolve_namespace(&self, prefix: &[u8]) -> Option<&[u8]> {
+        self.state.namespaces.resolve(prefix)
+    }
+}
+
+/// An [XmlRead] implementation which reads owned events from a buffered `io`
+/// source.
+pub struct IoReader<R> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    peeked: Option<Event<'static>>,
+    state: State,
+}
+
+impl<R> IoReader<R>
+where
+    R: BufRead,
+{
+    /// Wraps a quick-xml reader over a buffered `io` source. Note that the
+    /// reader will be configured to expand empty elements.
+    pub fn new(reader: Reader<R>) -> Self {
+        IoReader::with_options(reader, DecoderOptions::default())
+    }
+
+    /// Wraps a quick-xml reader over a buffered `io` source, decoding with
+    /// the given options.
+    pub fn with_options(mut reader: Reader<R>, options: DecoderOptions) -> Self {
+        configure(&mut reader);
+        IoReader {
+            reader,
+            buf: Vec::new(),
+            peeked: None,
+            state: State::new(options),
+        }
+    }
+
+    /// Unwraps the underlying quick-xml reader.
+    pub fn into_inner(self) -> Reader<R> {
+        self.reader
+    }
+
+    fn read_owned_event(&mut self) -> Result<Event<'static>> {
+        if let Some(event) = self.peeked.take() {
+            return Ok(event);
+        }
+
+        loop {
+            self.buf.clear();
+            let event = self
+                .reader
+                .read_event_into(&mut self.buf)
+                .map_err(DecodingError::from)?;
+            if !self.state.started || matches!(event, Event::Decl(_)) {
+                check_encoding(&self.reader)?;
+            }
+            let event = cdata_to_text(event, self.reader.decoder())?;
+            if let Some(event) = self.state.process(event)? {
+                return Ok(event.into_owned());
+            }
+        }
+    }
+}
+
+impl<'de, R> XmlRead<'de> for IoReader<R>
+where
+    R: BufRead,
+{
+    fn read_event(&mut self) -> Result<Event<'de>> {
+        self.read_owned_event()
+    }
+
+    fn peek_event(&mut self) -> Result<&Event<'de>> {
+        let event = self.read_owned_event()?;
+        Ok(self.peeked.insert(event))
+    }
+
+    fn options(&self) -> &DecoderOptions {
+        &self.state.options
+    }
+
+    fn resolve_namespace(&self, prefix: &[u8]) -> Option<&[u8]> {
+        self.state.namespaces.resolve(prefix)
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+
+    impl<'de> Sealed for super::SliceReader<'de> {}
+    impl<R> Sealed for super::IoReader<R> {}
+    impl<R> Sealed for &mut R where R: Sealed + ?Sized {}
+}
@@ -1,26 +1,366 @@
+//! Serialize a Rust data structure into xmlrpc data.
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+
 use base64::prelude::*;
 use quick_xml::{
     events::{BytesStart, Event},
     Writer,
 };
 
+use crate::encoding::OutputEncoding;
 use crate::error::EncodingError;
-use crate::xml_ext::WriterExt;
-use crate::{Error, Result};
+use crate::xml_ext::{WriterExt, EXTENSIONS_NS};
+use crate::{datetime, with};
+use crate::{Error, Result, Value};
+
+/// Options controlling how xmlrpc data is encoded. The defaults follow the
+/// XML-RPC spec.
+/// ```
+/// use serde_xmlrpc::{EncoderOptions, IntTag, Value};
+///
+/// let options = EncoderOptions::new()
+///     .int_tag(IntTag::I4)
+///     .string_tags(false)
+///     .xml_decl(false);
+/// let body = serde_xmlrpc::request_to_string_with(
+///     "add",
+///     vec![Value::Int(1), Value::String("two".to_string())].into_iter(),
+///     &options,
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     body,
+///     "<methodCall><methodName>add</methodName><params><param><value><i4>1</i4></value></param><param><value>two</value></param></params></methodCall>"
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct EncoderOptions {
+    pub(crate) extensions: bool,
+    pub(crate) int_tag: IntTag,
+    pub(crate) string_tags: bool,
+    pub(crate) xml_decl: bool,
+    pub(crate) decl_encoding: bool,
+    pub(crate) encoding: Option<Cow<'static, str>>,
+    pub(crate) allow_nil: bool,
+    pub(crate) self_closing_empty_strings: bool,
+    pub(crate) indent: Option<(u8, usize)>,
+}
+
+/// The tag used for 32-bit integers. The two are equivalent according to the
+/// XML-RPC spec, but some servers only accept one of them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IntTag {
+    /// `<int>`
+    #[default]
+    Int,
+    /// `<i4>`
+    I4,
+}
+
+impl IntTag {
+    fn name(self) -> &'static str {
+        match self {
+            IntTag::Int => "int",
+            IntTag::I4 => "i4",
+        }
+    }
+}
+
+impl EncoderOptions {
+    /// Creates options which follow the XML-RPC spec.
+    pub fn new() -> Self {
+        EncoderOptions::default()
+    }
+
+    /// Writes values with the extension types used by Apache ws-xmlrpc where
+    /// they match the Rust type: `ex:i1` for `i8`, `ex:i2` for `i16` and `u8`,
+    /// `ex:i8` for 64-bit integers, `ex:float` for `f32` and `ex:nil` for
+    /// missing values. The `ex` prefix is declared on the outermost element.
+    /// ```
+    /// use serde_xmlrpc::{EncoderOptions, Value};
+    ///
+    /// let options = EncoderOptions::new().extensions(true);
+    /// let body = serde_xmlrpc::value_to_string_with(Value::Int64(42), &options).unwrap();
+    /// assert_eq!(
+    ///     body,
+    ///     r#"<value xmlns:ex="http://ws.apache.org/xmlrpc/namespaces/extensions"><ex:i8>42</ex:i8></value>"#
+    /// );
+    /// ```
+    pub fn extensions(mut self, enabled: bool) -> Self {
+        self.extensions = enabled;
+        self
+    }
+
+    /// Sets the tag used for 32-bit integers, `<int>` by default.
+    pub fn int_tag(mut self, tag: IntTag) -> Self {
+        self.int_tag = tag;
+        self
+    }
+
+    /// Whether strings are wrapped in a `<string>` tag, which is the default.
+    /// Otherwise they are written as the bare text of the `<value>`, which
+    /// the spec treats the same.
+    pub fn string_tags(mut self, enabled: bool) -> Self {
+        self.string_tags = enabled;
+        self
+    }
+
+    /// Whether requests and responses start with an XML declaration, which is
+    /// the default. Standalone values never have one.
+    pub fn xml_decl(mut self, enabled: bool) -> Self {
+        self.xml_decl = enabled;
+        self
+    }
+
+    /// Whether the XML declaration names the encoding, which is the default.
+    /// The label is the one given to [encoding](EncoderOptions::encoding),
+    /// or `utf-8` if there is none.
+    ///
+    /// Readers assume UTF-8 without a label, so turning this or
+    /// [xml_decl](EncoderOptions::xml_decl) off makes encoding fail if the
+    /// output is in any other encoding.
+    pub fn decl_encoding(mut self, enabled: bool) -> Self {
+        self.decl_encoding = enabled;
+        self
+    }
+
+    /// Encodes requests and responses in the character encoding with the
+    /// given label, such as `ISO-8859-1` or `windows-1252`, and uses the
+    /// label in the XML declaration. Characters which the encoding can't
+    /// represent are written as numeric character references.
+    ///
+    /// As the output isn't UTF-8, it has to be written to bytes with one of
+    /// the `_writer_with` functions, such as
+    /// [request_to_writer_with](crate::request_to_writer_with) or
+    /// [fault_to_writer_with](crate::fault_to_writer_with). Standalone
+    /// values have no XML declaration to name the encoding in, so they can
+    /// only be written as UTF-8. Unknown labels, and encodings like UTF-16
+    /// which can't be written, fail when encoding.
+    /// ```
+    /// use serde_xmlrpc::{EncoderOptions, Value};
+    ///
+    /// let options = EncoderOptions::new().encoding("ISO-8859-1");
+    /// let mut body = Vec::new();
+    /// serde_xmlrpc::request_to_writer_with(
+    ///     &mut body,
+    ///     "greet",
+    ///     vec![Value::String("caf\u{e9} \u{263a}".to_string())].into_iter(),
+    ///     &options,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(
+    ///     body,
+    ///     &b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><methodCall><methodName>greet</methodName><params><param><value><string>caf\xe9 &#9786;</string></value></param></params></methodCall>"[..]
+    /// );
+    /// ```
+    pub fn encoding(mut self, label: &str) -> Self {
+        self.encoding = Some(Cow::Owned(label.to_owned()));
+        self
+    }
+
+    /// Returns the encoding requests and responses are written in.
+    pub(crate) fn output_encoding(&self) -> Result<OutputEncoding> {
+        match &self.encoding {
+            Some(label) => OutputEncoding::for_label(label),
+            None => Ok(OutputEncoding::Utf8),
+        }
+    }
+
+    /// Whether `None`, `()` and [Value::Nil] may be written as `<nil/>`,
+    /// which is the default. Otherwise encoding them fails, which is useful
+    /// for servers that don't support the nil extension.
+    pub fn allow_nil(mut self, enabled: bool) -> Self {
+        self.allow_nil = enabled;
+        self
+    }
+
+    /// Whether empty strings are written as a self-closing element, so
+    /// `<string/>` or `<value/>` without string tags, rather than as
+    /// `<string></string>` and `<value></value>`, which is the default.
+    pub fn self_closing_empty_strings(mut self, enabled: bool) -> Self {
+        self.self_closing_empty_strings = enabled;
+        self
+    }
+
+    /// Puts each element on its own line, indented by `indent_size` copies of
+    /// `indent_char` per level. Text content is written exactly as is, so no
+    /// whitespace is ever added to strings.
+    /// ```
+    /// use serde_xmlrpc::{EncoderOptions, Value};
+    ///
+    /// let options = EncoderOptions::new().indent(b' ', 2);
+    /// let value = Value::Array(vec![Value::Int(1), Value::String(" two ".to_string())]);
+    /// let body = serde_xmlrpc::value_to_string_with(value, &options).unwrap();
+    /// assert_eq!(
+    ///     body,
+    ///     "<value>
+    ///   <array>
+    ///     <data>
+    ///       <value>
+    ///         <int>1</int>
+    ///       </value>
+    ///       <value>
+    ///         <string> two </string>
+    ///       </value>
+    ///     </data>
+    ///   </array>
+    /// </value>"
+    /// );
+    /// ```
+    pub fn indent(mut self, indent_char: u8, indent_size: usize) -> Self {
+        self.indent = Some((indent_char, indent_size));
+        self
+    }
+
+    /// Creates a quick-xml [Writer] which formats its output as configured.
+    pub(crate) fn writer<W>(&self, inner: W) -> Writer<W>
+    where
+        W: std::io::Write,
+    {
+        match self.indent {
+            Some((indent_char, indent_size)) => {
+                Writer::new_with_indent(inner, indent_char, indent_size)
+            }
+            None => Writer::new(inner),
+        }
+    }
+
+    /// Returns the start tag of the outermost element, declaring any
+    /// namespaces the options need.
+    fn root_start(&self, tag: &'static str) -> BytesStart<'static> {
+        let mut start = BytesStart::new(tag);
+        if self.extensions {
+            start.push_attribute(("xmlns:ex", EXTENSIONS_NS));
+        }
+        start
+    }
+}
+
+impl Default for EncoderOptions {
+    fn default() -> Self {
+        DEFAULT_OPTIONS.clone()
+    }
+}
+
+static DEFAULT_OPTIONS: EncoderOptions = EncoderOptions {
+    extensions: false,
+    int_tag: IntTag::Int,
+    string_tags: true,
+    xml_decl: true,
+    decl_encoding: true,
+    encoding: None,
+    allow_nil: true,
+    self_closing_empty_strings: false,
+    indent: None,
+};
+
+/// Writes the XML declaration, if enabled, and the start tag of the document
+/// element.
+pub(crate) fn write_document_start<W>(
+    writer: &mut Writer<W>,
+    tag: &'static str,
+    options: &EncoderOptions,
+) -> Result<()>
+where
+    W: std::io::Write,
+{
+    let label = options.encoding.as_deref().unwrap_or("utf-8");
+    // Without a label, the output is read as UTF-8.
+    let labeled = options.xml_decl && options.decl_encoding;
+    if !labeled && !matches!(options.output_encoding()?, OutputEncoding::Utf8) {
+        return Err(EncodingError::UndeclaredEncoding(label.into()).into());
+    }
+    if options.xml_decl {
+        writer.write_decl(if labeled { Some(label) } else { None })?;
+    }
+    writer
+        .write_event(Event::Start(options.root_start(tag)))
+        .map_err(EncodingError::from)?;
+    Ok(())
+}
 
-pub(crate) struct Serializer<'a, W>
+/// A structure for serializing Rust values into xmlrpc values.
+///
+/// Each value is written as a single `<value>` element to a quick-xml
+/// [Writer], which makes it possible to embed xmlrpc values in a larger XML
+/// document.
+/// ```
+/// use serde::Serialize;
+/// use serde_xmlrpc::Serializer;
+///
+/// let mut writer = quick_xml::Writer::new(Vec::new());
+/// writer.create_element("wrapper").write_inner_content(|writer| {
+///     42.serialize(Serializer::new(writer)).unwrap();
+///     Ok::<_, quick_xml::Error>(())
+/// }).unwrap();
+///
+/// assert_eq!(
+///     writer.into_inner(),
+///     b"<wrapper><value><int>42</int></value></wrapper>"
+/// );
+/// ```
+pub struct Serializer<'a, W>
 where
     W: std::io::Write,
 {
     writer: &'a mut Writer<W>,
+    options: &'a EncoderOptions,
+    // Whether this is the outermost value, which namespaces are declared on.
+    root: bool,
 }
 
 impl<'a, W> Serializer<'a, W>
 where
     W: std::io::Write,
 {
-    pub(crate) fn new(writer: &'a mut Writer<W>) -> Self {
-        Serializer { writer }
+    /// Creates a serializer which writes to the given quick-xml [Writer].
+    pub fn new(writer: &'a mut Writer<W>) -> Self {
+        Serializer::with_options(writer, &DEFAULT_OPTIONS)
+    }
+
+    /// Creates a serializer which writes to the given quick-xml [Writer] with
+    /// the given options.
+    pub fn with_options(writer: &'a mut Writer<W>, options: &'a EncoderOptions) -> Self {
+        Serializer {
+            writer,
+            options,
+            root: true,
+        }
+    }
+
+    /// Creates a serializer for a value inside an element which was already
+    /// written with the same options.
+    pub(crate) fn nested(writer: &'a mut Writer<W>, options: &'a EncoderOptions) -> Self {
+        Serializer {
+            writer,
+            options,
+            root: false,
+        }
+    }
+
+    fn value_start(&self) -> BytesStart<'static> {
+        if self.root {
+            self.options.root_start("value")
+        } else {
+            BytesStart::new("value")
+        }
+    }
+
+    fn write_start_value(&mut self) -> Result<()> {
+        let start = self.value_start();
+        self.writer
+            .write_event(Event::Start(start))
+            .map_err(EncodingError::from)?;
+        Ok(())
+    }
+
+    fn write_value(mut self, tag: &str, text: &str) -> Result<()> {
+        self.write_start_value()?;
+        self.writer.write_safe_tag(tag, text)?;
+        self.writer.write_end_tag("value")?;
+        Ok(())
     }
 }
 
@@ -34,44 +374,54 @@ where
     type SerializeSeq = Self;
     type SerializeTuple = Self;
     type SerializeTupleStruct = Self;
-    type SerializeTupleVariant = Self;
+    type SerializeTupleVariant = SerializeVariant<'a, W>;
     type SerializeMap = Self;
     type SerializeStruct = Self;
-    type SerializeStructVariant = Self;
+    type SerializeStructVariant = SerializeVariant<'a, W>;
 
     fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
-        self.writer.write_start_tag("value")?;
-        self.writer
-            .write_safe_tag("boolean", if v { "1" } else { "0" })?;
-        self.writer.write_end_tag("value")?;
-        Ok(())
+        self.write_value("boolean", if v { "1" } else { "0" })
     }
 
     fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
-        self.serialize_i64(v as i64)
+        if self.options.extensions {
+            return self.write_value("ex:i1", &v.to_string());
+        }
+        self.serialize_i32(v as i32)
     }
 
     fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
-        self.serialize_i64(v as i64)
+        if self.options.extensions {
+            return self.write_value("ex:i2", &v.to_string());
+        }
+        self.serialize_i32(v as i32)
     }
 
     fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
-        self.serialize_i64(v as i64)
+        let tag = self.options.int_tag.name();
+        self.write_value(tag, &v.to_string())
     }
 
     fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
-        self.writer.write_start_tag("value")?;
-        self.writer.write_safe_tag("int", &v.to_string())?;
-        self.writer.write_end_tag("value")?;
-        Ok(())
+        // Values which don't fit in 32 bits need the i8 extension tag, as
+        // "int" is only allowed to hold an i32.
+        let tag = if self.options.extensions {
+            "ex:i8"
+        } else if i32::try_from(v).is_ok() {
+            self.options.int_tag.name()
+        } else {
+            "i8"
+        };
+
+        self.write_value(tag, &v.to_string())
     }
 
     fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
-        self.serialize_u64(v as u64)
+        self.serialize_i16(v as i16)
     }
 
     fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
-        self.serialize_u64(v as u64)
+        self.serialize_i32(v as i32)
     }
 
     fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
@@ -79,43 +429,51 @@ where
     }
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
-        self.writer.write_start_tag("value")?;
-        self.writer.write_safe_tag("int", &v.to_string())?;
-        self.writer.write_end_tag("value")?;
-        Ok(())
+        let v = i64::try_from(v).map_err(|_| EncodingError::IntegerOutOfRange(v))?;
+        self.serialize_i64(v)
     }
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        if self.options.extensions {
+            return self.write_value("ex:float", &v.to_string());
+        }
         self.serialize_f64(v as f64)
     }
 
     fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
-        self.writer.write_start_tag("value")?;
-        self.writer.write_safe_tag("double", &v.to_string())?;
-        self.writer.write_end_tag("value")?;
-        Ok(())
+        self.write_value("double", &v.to_string())
     }
 
     fn serialize_char(self, v: char) -> Result<Self::Ok> {
-        self.writer.write_start_tag("value")?;
-        self.writer.write_tag("string", &v.to_string())?;
-        self.writer.write_end_tag("value")?;
-        Ok(())
-    }
-
-    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
-        self.writer.write_start_tag("value")?;
-        self.writer.write_tag("string", v)?;
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_str(mut self, v: &str) -> Result<Self::Ok> {
+        let self_closing = v.is_empty() && self.options.self_closing_empty_strings;
+        if self_closing && !self.options.string_tags {
+            let start = self.value_start();
+            self.writer
+                .write_event(Event::Empty(start))
+                .map_err(EncodingError::from)?;
+            return Ok(());
+        }
+
+        self.write_start_value()?;
+        if self_closing {
+            self.writer
+                .write_event(Event::Empty(BytesStart::new("string")))
+                .map_err(EncodingError::from)?;
+        } else if self.options.string_tags {
+            self.writer.write_tag("string", v)?;
+        } else {
+            self.writer.write_text(v)?;
+        }
         self.writer.write_end_tag("value")?;
         Ok(())
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
-        self.writer.write_start_tag("value")?;
-        self.writer
-            .write_safe_tag("base64", &BASE64_STANDARD.encode(v))?;
-        self.writer.write_end_tag("value")?;
-        Ok(())
+        self.write_value("base64", &BASE64_STANDARD.encode(v))
     }
 
     fn serialize_none(self) -> Result<Self::Ok> {
@@ -129,10 +487,20 @@ where
         v.serialize(self)
     }
 
-    fn serialize_unit(self) -> Result<Self::Ok> {
-        self.writer.write_start_tag("value")?;
+    fn serialize_unit(mut self) -> Result<Self::Ok> {
+        if !self.options.allow_nil {
+            return Err(EncodingError::NilNotAllowed.into());
+        }
+
+        let tag = if self.options.extensions {
+            "ex:nil"
+        } else {
+            "nil"
+        };
+
+        self.write_start_value()?;
         self.writer
-            .write_event(Event::Empty(BytesStart::new("nil")))
+            .write_event(Event::Empty(BytesStart::new(tag)))
             .map_err(EncodingError::from)?;
         self.writer.write_end_tag("value")?;
         Ok(())
@@ -146,15 +514,46 @@ where
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
     ) -> Result<Self::Ok> {
-        self.serialize_unit()
+        self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
+    fn serialize_newtype_struct<T>(mut self, name: &'static str, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + serde::Serialize,
     {
+        if name == datetime::TOKEN {
+            let text = match value.serialize(crate::value::Serializer::new())? {
+                Value::String(text) => text,
+                _ => return Err(serde::ser::Error::custom("dateTime must be a string")),
+            };
+
+            self.write_start_value()?;
+            self.writer.write_tag("dateTime.iso8601", &text)?;
+            self.writer.write_end_tag("value")?;
+            return Ok(());
+        }
+
+        if name == with::I8_TOKEN {
+            let v = match value.serialize(crate::value::Serializer::new())? {
+                Value::Int(v) => i64::from(v),
+                Value::Int64(v) => v,
+                _ => return Err(serde::ser::Error::custom("i8 must be an integer")),
+            };
+
+            let tag = if self.options.extensions {
+                "ex:i8"
+            } else {
+                "i8"
+            };
+            return self.write_value(tag, &v.to_string());
+        }
+
+        if name == with::NIL_TOKEN {
+            return self.serialize_unit();
+        }
+
         value.serialize(self)
     }
 
@@ -162,18 +561,21 @@ where
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
+        variant: &'static str,
+        value: &T,
     ) -> Result<Self::Ok>
     where
         T: ?Sized + serde::Serialize,
     {
-        // TODO: replace with Error
-        unimplemented!();
+        // Data variants are written as a struct with a single member named
+        // after the variant, the same as serde_json.
+        let mut map = self.serialize_map(Some(1))?;
+        serde::ser::SerializeMap::serialize_entry(&mut map, variant, value)?;
+        serde::ser::SerializeMap::end(map)
     }
 
-    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        self.writer.write_start_tag("value")?;
+    fn serialize_seq(mut self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        self.write_start_value()?;
         self.writer.write_start_tag("array")?;
         self.writer.write_start_tag("data")?;
         Ok(self)
@@ -192,17 +594,26 @@ where
     }
 
     fn serialize_tuple_variant(
-        self,
+        mut self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        self.serialize_seq(Some(len))
+        self.write_start_value()?;
+        self.writer.write_start_tag("struct")?;
+        self.writer.write_start_tag("member")?;
+        self.writer.write_tag("name", variant)?;
+
+        let Serializer {
+            writer, options, ..
+        } = self;
+        Serializer::nested(&mut *writer, options).serialize_seq(Some(len))?;
+        Ok(SerializeVariant { writer, options })
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        self.writer.write_start_tag("value")?;
+    fn serialize_map(mut self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        self.write_start_value()?;
         self.writer.write_start_tag("struct")?;
         Ok(self)
     }
@@ -212,13 +623,22 @@ where
     }
 
     fn serialize_struct_variant(
-        self,
+        mut self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        self.serialize_map(Some(len))
+        self.write_start_value()?;
+        self.writer.write_start_tag("struct")?;
+        self.writer.write_start_tag("member")?;
+        self.writer.write_tag("name", variant)?;
+
+        let Serializer {
+            writer, options, ..
+        } = self;
+        Serializer::nested(&mut *writer, options).serialize_map(Some(len))?;
+        Ok(SerializeVariant { writer, options })
     }
 }
 
@@ -233,7 +653,7 @@ where
     where
         T: ?Sized + serde::Serialize,
     {
-        value.serialize(Serializer::new(self.writer))
+        value.serialize(Serializer::nested(self.writer, self.options))
     }
 
     fn end(self) -> Result<Self::Ok> {
@@ -283,25 +703,6 @@ where
     }
 }
 
-impl<'a, W> serde::ser::SerializeTupleVariant for Serializer<'a, W>
-where
-    W: std::io::Write,
-{
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
-    where
-        T: ?Sized + serde::Serialize,
-    {
-        serde::ser::SerializeSeq::serialize_element(self, value)
-    }
-
-    fn end(self) -> Result<Self::Ok> {
-        serde::ser::SerializeSeq::end(self)
-    }
-}
-
 impl<'a, W> serde::ser::SerializeMap for Serializer<'a, W>
 where
     W: std::io::Write,
@@ -322,7 +723,7 @@ where
     where
         T: ?Sized + serde::Serialize,
     {
-        value.serialize(Serializer::new(self.writer))?;
+        value.serialize(Serializer::nested(self.writer, self.options))?;
         self.writer.write_end_tag("member")?;
         Ok(())
     }
@@ -353,7 +754,50 @@ where
     }
 }
 
-impl<'a, W> serde::ser::SerializeStructVariant for Serializer<'a, W>
+/// Wraps the array or struct of a tuple or struct variant in an outer struct
+/// with a single member named after the variant.
+#[doc(hidden)]
+pub struct SerializeVariant<'a, W>
+where
+    W: std::io::Write,
+{
+    writer: &'a mut Writer<W>,
+    options: &'a EncoderOptions,
+}
+
+impl<'a, W> SerializeVariant<'a, W>
+where
+    W: std::io::Write,
+{
+    fn end(writer: &mut Writer<W>) -> Result<()> {
+        writer.write_end_tag("member")?;
+        writer.write_end_tag("struct")?;
+        writer.write_end_tag("value")?;
+        Ok(())
+    }
+}
+
+impl<'a, W> serde::ser::SerializeTupleVariant for SerializeVariant<'a, W>
+where
+    W: std::io::Write,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        value.serialize(Serializer::nested(self.writer, self.options))
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        serde::ser::SerializeSeq::end(Serializer::nested(&mut *self.writer, self.options))?;
+        SerializeVariant::end(self.writer)
+    }
+}
+
+impl<'a, W> serde::ser::SerializeStructVariant for SerializeVariant<'a, W>
 where
     W: std::io::Write,
 {
@@ -364,11 +808,13 @@ where
     where
         T: ?Sized + serde::Serialize,
     {
-        serde::ser::SerializeMap::serialize_entry(self, key, value)
+        let mut ser = Serializer::nested(self.writer, self.options);
+        serde::ser::SerializeMap::serialize_entry(&mut ser, key, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        serde::ser::SerializeMap::end(self)
+        serde::ser::SerializeMap::end(Serializer::nested(&mut *self.writer, self.options))?;
+        SerializeVariant::end(self.writer)
     }
 }
 
@@ -483,9 +929,9 @@ where
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
     ) -> Result<Self::Ok> {
-        Err(key_must_be_a_string())
+        self.serialize_str(variant)
     }
 
     fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<Self::Ok>
@@ -556,3 +1002,252 @@ where
 fn key_must_be_a_string() -> Error {
     Error::from(crate::error::DecodingError::KeyMustBeString)
 }
+
+/// Serializes a sequence or tuple as a list of `<param>` tags, one per
+/// element, rather than as a single `<array>` value.
+pub(crate) struct ParamsSerializer<'a, W>
+where
+    W: std::io::Write,
+{
+    writer: &'a mut Writer<W>,
+    options: &'a EncoderOptions,
+}
+
+impl<'a, W> ParamsSerializer<'a, W>
+where
+    W: std::io::Write,
+{
+    pub(crate) fn new(writer: &'a mut Writer<W>, options: &'a EncoderOptions) -> Self {
+        ParamsSerializer { writer, options }
+    }
+}
+
+impl<'a, W> serde::Serializer for ParamsSerializer<'a, W>
+where
+    W: std::io::Write,
+{
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = serde::ser::Impossible<Self::Ok, Self::Error>;
+    type SerializeMap = serde::ser::Impossible<Self::Ok, Self::Error>;
+    type SerializeStruct = serde::ser::Impossible<Self::Ok, Self::Error>;
+    type SerializeStructVariant = serde::ser::Impossible<Self::Ok, Self::Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<Self::Ok> {
+        Err(params_must_be_a_sequence())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<Self::Ok> {
+        Err(params_must_be_a_sequence())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<Self::Ok> {
+        Err(params_must_be_a_sequence())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Self::Ok> {
+        Err(params_must_be_a_sequence())
+    }
+
+    fn serialize_char(self, _v: char) -> Result<Self::Ok> {
+        Err(params_must_be_a_sequence())
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<Self::Ok> {
+        Err(params_must_be_a_sequence())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok> {
+        Err(params_must_be_a_sequence())
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Err(params_must_be_a_sequence())
+    }
+
+    fn serialize_some<T>(self, _v: &T) -> Result<Self::Ok>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        Err(params_must_be_a_sequence())
+    }
+
+    // A unit value is treated as an empty list of params, which makes calling
+    // methods without any arguments a bit more natural.
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Self::Ok> {
+        Err(params_must_be_a_sequence())
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        Err(params_must_be_a_sequence())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(params_must_be_a_sequence())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(params_must_be_a_sequence())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(params_must_be_a_sequence())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(params_must_be_a_sequence())
+    }
+}
+
+impl<'a, W> serde::ser::SerializeSeq for ParamsSerializer<'a, W>
+where
+    W: std::io::Write,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.writer.write_start_tag("param")?;
+        value.serialize(Serializer::nested(self.writer, self.options))?;
+        self.writer.write_end_tag("param")?;
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(())
+    }
+}
+
+impl<'a, W> serde::ser::SerializeTuple for ParamsSerializer<'a, W>
+where
+    W: std::io::Write,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        serde::ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        serde::ser::SerializeSeq::end(self)
+    }
+}
+
+impl<'a, W> serde::ser::SerializeTupleStruct for ParamsSerializer<'a, W>
+where
+    W: std::io::Write,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        serde::ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        serde::ser::SerializeSeq::end(self)
+    }
+}
+
+fn params_must_be_a_sequence() -> Error {
+    Error::from(EncodingError::ParamsMustBeSequence)
+}
@@ -1,8 +1,11 @@
 use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
 
-use serde::de::Visitor;
+use serde::de::{Deserialize, IntoDeserializer, VariantAccess, Visitor};
 use serde::forward_to_deserialize_any;
 
+use crate::datetime::{self, DateTimeAccess};
 use crate::{Error, Result, Value};
 
 impl<'de> serde::Deserializer<'de> for Value {
@@ -18,7 +21,7 @@ impl<'de> serde::Deserializer<'de> for Value {
             Value::Bool(v) => visitor.visit_bool(v),
             Value::String(v) => visitor.visit_string(v),
             Value::Double(v) => visitor.visit_f64(v),
-            Value::DateTime(v) => visitor.visit_string(v.to_string()),
+            Value::DateTime(v) => visitor.visit_string(datetime::format(&v)),
             Value::Base64(v) => visitor.visit_bytes(v.as_slice()),
             Value::Struct(v) => {
                 let map_deserializer = MapDeserializer::new(v);
@@ -43,13 +46,95 @@ impl<'de> serde::Deserializer<'de> for Value {
         }
     }
 
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::DateTime(v) if name == datetime::VALUE_TOKEN => {
+                visitor.visit_enum(DateTimeAccess::new(datetime::format(&v).into()))
+            }
+            value if name == datetime::VALUE_TOKEN => value.deserialize_any(visitor),
+            value => visitor.visit_newtype_struct(value),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // Unit variants are stored as a string and data variants as a struct
+        // with a single member named after the variant. Anything else is
+        // passed on as is so the visitor can raise an error.
+        match self {
+            Value::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Value::Struct(v) if v.len() == 1 => match v.into_iter().next() {
+                Some((variant, value)) => visitor.visit_enum(EnumDeserializer { variant, value }),
+                None => Err(serde::de::Error::invalid_length(0, &"a single member")),
+            },
+            value => value.deserialize_any(visitor),
+        }
+    }
+
     forward_to_deserialize_any!(
         bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
-        byte_buf unit unit_struct newtype_struct seq tuple
-        tuple_struct map struct enum identifier ignored_any
+        byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
     );
 }
 
+struct EnumDeserializer {
+    variant: String,
+    value: Value,
+}
+
+impl<'de> serde::de::EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = Value;
+
+    fn variant_seed<T>(self, seed: T) -> Result<(T::Value, Value)>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(Value::String(self.variant))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> serde::de::VariantAccess<'de> for Value {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        serde::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        serde::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
 struct SeqDeserializer {
     iter: std::vec::IntoIter<Value>,
 }
@@ -129,12 +214,147 @@ impl<'de> serde::Deserialize<'de> for Value {
     where
         D: serde::Deserializer<'de>,
     {
-        // TODO: this isn't quite right - the error type doesn't match up.
-        // Unfortunately, there doesn't seem to be a way to solve this, as it
-        // requires the error type to match up between the serializer and
-        // deserializer, and they have incompatible error types.
-        serde_transcode::transcode(deserializer, super::Serializer::new())
-            .map_err(serde::de::Error::custom)
+        // Our own deserializers recognize this name and pass dateTime values
+        // on as a marked map rather than a plain string.
+        deserializer.deserialize_newtype_struct(datetime::VALUE_TOKEN, ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any valid xmlrpc value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> std::result::Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i8<E>(self, v: i8) -> std::result::Result<Value, E> {
+        Ok(Value::Int(v as i32))
+    }
+
+    fn visit_i16<E>(self, v: i16) -> std::result::Result<Value, E> {
+        Ok(Value::Int(v as i32))
+    }
+
+    fn visit_i32<E>(self, v: i32) -> std::result::Result<Value, E> {
+        Ok(Value::Int(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> std::result::Result<Value, E> {
+        Ok(Value::Int64(v))
+    }
+
+    fn visit_u8<E>(self, v: u8) -> std::result::Result<Value, E> {
+        Ok(Value::Int(v as i32))
+    }
+
+    fn visit_u16<E>(self, v: u16) -> std::result::Result<Value, E> {
+        Ok(Value::Int(v as i32))
+    }
+
+    fn visit_u32<E>(self, v: u32) -> std::result::Result<Value, E> {
+        Ok(Value::Int64(v as i64))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> std::result::Result<Value, E>
+    where
+        E: serde::de::Error,
+    {
+        match i64::try_from(v) {
+            Ok(v) => Ok(Value::Int64(v)),
+            Err(_) => Err(E::invalid_value(
+                serde::de::Unexpected::Unsigned(v),
+                &"an integer which fits in an i64",
+            )),
+        }
+    }
+
+    fn visit_f64<E>(self, v: f64) -> std::result::Result<Value, E> {
+        Ok(Value::Double(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> std::result::Result<Value, E> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn visit_string<E>(self, v: String) -> std::result::Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> std::result::Result<Value, E> {
+        Ok(Value::Base64(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> std::result::Result<Value, E> {
+        Ok(Value::Base64(v))
+    }
+
+    fn visit_none<E>(self) -> std::result::Result<Value, E> {
+        Ok(Value::Nil)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> std::result::Result<Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_unit<E>(self) -> std::result::Result<Value, E> {
+        Ok(Value::Nil)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> std::result::Result<Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut vec = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            vec.push(value);
+        }
+        Ok(Value::Array(vec))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> std::result::Result<Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let mut values = BTreeMap::new();
+        while let Some((key, value)) = map.next_entry()? {
+            values.insert(key, value);
+        }
+
+        Ok(Value::Struct(values))
+    }
+
+    fn visit_enum<A>(self, data: A) -> std::result::Result<Value, A::Error>
+    where
+        A: serde::de::EnumAccess<'de>,
+    {
+        // An enum with the private dateTime variant is how our deserializers
+        // mark a dateTime value.
+        let (variant, access) = data.variant::<String>()?;
+        if variant != datetime::TOKEN {
+            return Err(serde::de::Error::invalid_type(
+                serde::de::Unexpected::Enum,
+                &self,
+            ));
+        }
+        let text: String = access.newtype_variant()?;
+        let dt = datetime::parse(&text).map_err(serde::de::Error::custom)?;
+        Ok(Value::DateTime(dt))
     }
 }
 
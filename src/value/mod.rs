@@ -1,3 +1,5 @@
+//! The [Value] type and conversions between it and Rust data structures.
+
 use std::{collections::BTreeMap, convert::TryFrom};
 
 use iso8601::DateTime;
@@ -5,7 +7,7 @@ use iso8601::DateTime;
 mod de;
 mod ser;
 
-pub(crate) use ser::Serializer;
+pub use ser::Serializer;
 
 /// Convert a `T` into `serde_xmlrpc::Value` which is an enum that can represent
 /// any valid XML-RPC data.
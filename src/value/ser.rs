@@ -1,8 +1,10 @@
 use std::collections::BTreeMap;
+use std::convert::TryFrom;
 
 use serde::Serialize;
 
 use crate::error::EncodingError;
+use crate::{datetime, with};
 use crate::{Error, Result, Value};
 
 impl serde::ser::Serialize for Value {
@@ -16,7 +18,9 @@ impl serde::ser::Serialize for Value {
             Value::Bool(b) => serializer.serialize_bool(*b),
             Value::Double(f) => serializer.serialize_f64(*f),
             Value::String(s) => serializer.serialize_str(s),
-            Value::DateTime(dt) => serializer.serialize_str(&dt.to_string()),
+            Value::DateTime(dt) => {
+                serializer.serialize_newtype_struct(datetime::TOKEN, &datetime::format(dt))
+            }
             Value::Base64(b) => serializer.serialize_bytes(b),
             Value::Array(a) => a.serialize(serializer),
             Value::Struct(m) => m.serialize(serializer),
@@ -25,9 +29,13 @@ impl serde::ser::Serialize for Value {
     }
 }
 
-pub(crate) struct Serializer;
+/// A serializer which converts Rust values into a [Value] rather than
+/// writing them out as XML. This is what [to_value](crate::to_value) uses.
+#[derive(Default)]
+pub struct Serializer;
 
 impl Serializer {
+    /// Creates a new [Value] serializer.
     pub fn new() -> Self {
         Serializer {}
     }
@@ -40,10 +48,10 @@ impl serde::Serializer for Serializer {
     type SerializeSeq = SerializeVec;
     type SerializeTuple = SerializeVec;
     type SerializeTupleStruct = SerializeVec;
-    type SerializeTupleVariant = SerializeVec;
+    type SerializeTupleVariant = SerializeTupleVariant;
     type SerializeMap = SerializeMap;
     type SerializeStruct = SerializeMap;
-    type SerializeStructVariant = SerializeMap;
+    type SerializeStructVariant = SerializeStructVariant;
 
     fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
         Ok(Value::Bool(v))
@@ -77,12 +85,11 @@ impl serde::Serializer for Serializer {
         Ok(Value::Int64(v as i64))
     }
 
-    fn serialize_u64(self, _v: u64) -> Result<Self::Ok> {
-        // This type doesn't fit inside an i32 or i64 which are the only
-        // officially supported int types in xmlrpc.
-
-        // TODO: replace with Error
-        unimplemented!();
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        // Values which don't fit inside an i64 can't be represented, as i32
+        // and i64 are the only supported int types in xmlrpc.
+        let v = i64::try_from(v).map_err(|_| EncodingError::IntegerOutOfRange(v))?;
+        Ok(Value::Int64(v))
     }
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
@@ -128,15 +135,36 @@ impl serde::Serializer for Serializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
     ) -> Result<Self::Ok> {
-        self.serialize_unit()
+        self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
+    fn serialize_newtype_struct<T: ?Sized>(self, name: &'static str, value: &T) -> Result<Self::Ok>
     where
         T: Serialize,
     {
+        if name == datetime::TOKEN {
+            return match value.serialize(self)? {
+                Value::String(text) => Ok(Value::DateTime(
+                    datetime::parse(&text).map_err(<Error as serde::ser::Error>::custom)?,
+                )),
+                _ => Err(serde::ser::Error::custom("dateTime must be a string")),
+            };
+        }
+
+        if name == with::I8_TOKEN {
+            return match value.serialize(self)? {
+                Value::Int(v) => Ok(Value::Int64(i64::from(v))),
+                Value::Int64(v) => Ok(Value::Int64(v)),
+                _ => Err(serde::ser::Error::custom("i8 must be an integer")),
+            };
+        }
+
+        if name == with::NIL_TOKEN {
+            return Ok(Value::Nil);
+        }
+
         value.serialize(self)
     }
 
@@ -144,14 +172,15 @@ impl serde::Serializer for Serializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
+        variant: &'static str,
+        value: &T,
     ) -> Result<Self::Ok>
     where
         T: Serialize,
     {
-        // TODO: match implementation with serde_json
-        unimplemented!();
+        let mut map = BTreeMap::new();
+        map.insert(variant.to_string(), value.serialize(self)?);
+        Ok(Value::Struct(map))
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
@@ -176,10 +205,13 @@ impl serde::Serializer for Serializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        self.serialize_tuple(len)
+        Ok(SerializeTupleVariant {
+            name: variant.to_string(),
+            vec: Vec::with_capacity(len),
+        })
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
@@ -197,15 +229,18 @@ impl serde::Serializer for Serializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        len: usize,
+        variant: &'static str,
+        _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        // TODO: match implementation with serde_json
-        self.serialize_map(Some(len))
+        Ok(SerializeStructVariant {
+            name: variant.to_string(),
+            map: BTreeMap::new(),
+        })
     }
 }
 
-pub(crate) struct SerializeVec {
+#[doc(hidden)]
+pub struct SerializeVec {
     vec: Vec<Value>,
 }
 
@@ -258,7 +293,13 @@ impl serde::ser::SerializeTupleStruct for SerializeVec {
     }
 }
 
-impl serde::ser::SerializeTupleVariant for SerializeVec {
+#[doc(hidden)]
+pub struct SerializeTupleVariant {
+    name: String,
+    vec: Vec<Value>,
+}
+
+impl serde::ser::SerializeTupleVariant for SerializeTupleVariant {
     type Ok = Value;
     type Error = Error;
 
@@ -266,15 +307,19 @@ impl serde::ser::SerializeTupleVariant for SerializeVec {
     where
         T: Serialize,
     {
-        serde::ser::SerializeSeq::serialize_element(self, value)
+        self.vec.push(value.serialize(Serializer)?);
+        Ok(())
     }
 
     fn end(self) -> Result<Self::Ok> {
-        serde::ser::SerializeSeq::end(self)
+        let mut map = BTreeMap::new();
+        map.insert(self.name, Value::Array(self.vec));
+        Ok(Value::Struct(map))
     }
 }
 
-pub(crate) struct SerializeMap {
+#[doc(hidden)]
+pub struct SerializeMap {
     map: BTreeMap<String, Value>,
     next_key: Option<String>,
 }
@@ -303,10 +348,7 @@ impl serde::ser::SerializeMap for SerializeMap {
     where
         T: Serialize,
     {
-        let key = self
-            .next_key
-            .take()
-            .expect("serialize_value called before serialize_key");
+        let key = self.next_key.take().ok_or(EncodingError::MissingKey)?;
         let value = value.serialize(Serializer)?;
 
         self.map.insert(key, value);
@@ -335,7 +377,13 @@ impl serde::ser::SerializeStruct for SerializeMap {
     }
 }
 
-impl serde::ser::SerializeStructVariant for SerializeMap {
+#[doc(hidden)]
+pub struct SerializeStructVariant {
+    name: String,
+    map: BTreeMap<String, Value>,
+}
+
+impl serde::ser::SerializeStructVariant for SerializeStructVariant {
     type Ok = Value;
     type Error = Error;
 
@@ -343,11 +391,15 @@ impl serde::ser::SerializeStructVariant for SerializeMap {
     where
         T: Serialize,
     {
-        serde::ser::SerializeMap::serialize_entry(self, key, value)
+        self.map
+            .insert(key.to_string(), value.serialize(Serializer)?);
+        Ok(())
     }
 
     fn end(self) -> Result<Value> {
-        serde::ser::SerializeMap::end(self)
+        let mut map = BTreeMap::new();
+        map.insert(self.name, Value::Struct(self.map));
+        Ok(Value::Struct(map))
     }
 }
 
@@ -414,4 +466,12 @@ mod test {
         let y = y.serialize(Serializer).unwrap();
         assert_eq!(y, x,);
     }
+
+    #[test]
+    fn test_value_without_key() {
+        use serde::ser::{SerializeMap, Serializer as _};
+
+        let mut map = Serializer.serialize_map(None).unwrap();
+        assert!(map.serialize_value(&1).is_err());
+    }
 }
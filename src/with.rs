@@ -0,0 +1,395 @@
+//! Helpers for forcing a specific xmlrpc type for a value, either as modules
+//! for use with serde's `with` attribute or as newtypes.
+//!
+//! Our serializers recognize these through newtype structs with private
+//! names, while any other format just sees the inner value.
+
+use std::fmt;
+
+use serde::de::VariantAccess;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::datetime::DateTimeField;
+
+/// The newtype struct name which marks an integer to be written as `<i8>`.
+pub(crate) const I8_TOKEN: &str = "$serde_xmlrpc::private::I8";
+
+/// The newtype struct name which marks a `<nil/>` value.
+pub(crate) const NIL_TOKEN: &str = "$serde_xmlrpc::private::Nil";
+
+/// Serializes bytes as a `<base64>` value rather than an `<array>` of
+/// `<int>`, which is what a `Vec<u8>` would otherwise be written as.
+/// ```
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct File {
+///     #[serde(with = "serde_xmlrpc::as_base64")]
+///     data: Vec<u8>,
+/// }
+///
+/// let file = File { data: b"hello".to_vec() };
+/// let body = serde_xmlrpc::value_to_string(&file).unwrap();
+/// assert_eq!(
+///     body,
+///     "<value><struct><member><name>data</name><value><base64>aGVsbG8=</base64></value></member></struct></value>"
+/// );
+///
+/// let parsed: File = serde_xmlrpc::value_from_str(&body).unwrap();
+/// assert_eq!(parsed, file);
+/// ```
+pub mod as_base64 {
+    use super::*;
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + AsRef<[u8]>,
+        S: Serializer,
+    {
+        serializer.serialize_bytes(value.as_ref())
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: From<Vec<u8>>,
+        D: Deserializer<'de>,
+    {
+        Base64::deserialize(deserializer).map(|b| b.0.into())
+    }
+}
+
+/// Serializes a date as a `<dateTime.iso8601>` value rather than whatever
+/// its own `Serialize` implementation would produce, which is usually a
+/// `<string>`. This works for any [DateTimeField], which includes `String`
+/// and every [XmlRpcDateTime](crate::datetime::XmlRpcDateTime).
+///
+/// When deserializing, both `<dateTime.iso8601>` values and plain strings
+/// are accepted.
+/// ```
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Event {
+///     #[serde(with = "serde_xmlrpc::as_datetime")]
+///     at: iso8601::DateTime,
+///     #[serde(with = "serde_xmlrpc::as_datetime")]
+///     until: String,
+/// }
+///
+/// let event = Event {
+///     at: iso8601::datetime("19980717T14:08:55").unwrap(),
+///     until: "1998-07-18T00:00:00Z".to_string(),
+/// };
+///
+/// let body = serde_xmlrpc::value_to_string(&event).unwrap();
+/// assert_eq!(
+///     body,
+///     "<value><struct><member><name>at</name><value><dateTime.iso8601>19980717T14:08:55</dateTime.iso8601></value></member><member><name>until</name><value><dateTime.iso8601>1998-07-18T00:00:00Z</dateTime.iso8601></value></member></struct></value>"
+/// );
+///
+/// let parsed: Event = serde_xmlrpc::value_from_str(&body).unwrap();
+/// assert_eq!(parsed, event);
+/// ```
+pub mod as_datetime {
+    use super::*;
+
+    use crate::datetime;
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: DateTimeField,
+        S: Serializer,
+    {
+        let text = value
+            .to_datetime_text()
+            .map_err(serde::ser::Error::custom)?;
+        serializer.serialize_newtype_struct(datetime::TOKEN, &text)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: DateTimeField,
+        D: Deserializer<'de>,
+    {
+        // Asking for a Value makes our deserializers mark dateTime values,
+        // while other formats will hand us a plain string.
+        let text =
+            deserializer.deserialize_newtype_struct(datetime::VALUE_TOKEN, DateTimeVisitor)?;
+        T::from_datetime_text(&text).map_err(serde::de::Error::custom)
+    }
+
+    struct DateTimeVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for DateTimeVisitor {
+        type Value = String;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an ISO 8601 date and time")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(v.to_string())
+        }
+
+        fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(v)
+        }
+
+        fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_string(self)
+        }
+
+        fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::EnumAccess<'de>,
+        {
+            match data.variant::<String>()? {
+                (variant, access) if variant == datetime::TOKEN => access.newtype_variant(),
+                _ => Err(serde::de::Error::custom("expected a dateTime value")),
+            }
+        }
+    }
+}
+
+/// Serializes an integer as an `<i8>` value, even if it would fit in an
+/// `<int>`. Some servers expect 64-bit fields to always use the same tag.
+/// ```
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Counter {
+///     #[serde(with = "serde_xmlrpc::as_i8")]
+///     count: i32,
+/// }
+///
+/// let body = serde_xmlrpc::value_to_string(&Counter { count: 42 }).unwrap();
+/// assert_eq!(
+///     body,
+///     "<value><struct><member><name>count</name><value><i8>42</i8></value></member></struct></value>"
+/// );
+///
+/// let parsed: Counter = serde_xmlrpc::value_from_str(&body).unwrap();
+/// assert_eq!(parsed, Counter { count: 42 });
+/// ```
+pub mod as_i8 {
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Copy + Into<i64>,
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(I8_TOKEN, &(*value).into())
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: TryFrom<i64>,
+        D: Deserializer<'de>,
+    {
+        let value = i64::deserialize(deserializer)?;
+        T::try_from(value).map_err(|_| {
+            serde::de::Error::invalid_value(
+                serde::de::Unexpected::Signed(value),
+                &"an integer in range",
+            )
+        })
+    }
+}
+
+/// Serializes an `Option` as an explicit `<nil/>` value when it is `None`,
+/// rather than leaving it up to the serializer how missing values are
+/// written.
+/// ```
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct User {
+///     #[serde(with = "serde_xmlrpc::as_nil_if_none")]
+///     email: Option<String>,
+/// }
+///
+/// let body = serde_xmlrpc::value_to_string(&User { email: None }).unwrap();
+/// assert_eq!(
+///     body,
+///     "<value><struct><member><name>email</name><value><nil/></value></member></struct></value>"
+/// );
+///
+/// let parsed: User = serde_xmlrpc::value_from_str(&body).unwrap();
+/// assert_eq!(parsed, User { email: None });
+/// ```
+pub mod as_nil_if_none {
+    use super::*;
+
+    pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        match value {
+            Some(value) => value.serialize(serializer),
+            None => Nil.serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Option::<T>::deserialize(deserializer)
+    }
+}
+
+/// Binary data which is always written as a `<base64>` value. See
+/// [as_base64] for a version which can be used on fields directly.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Base64(pub Vec<u8>);
+
+impl Serialize for Base64 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Base64 {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct BytesVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("binary data")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(v.to_vec())
+            }
+
+            fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(v)
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(b) = seq.next_element()? {
+                    bytes.push(b);
+                }
+                Ok(bytes)
+            }
+        }
+
+        deserializer.deserialize_byte_buf(BytesVisitor).map(Base64)
+    }
+}
+
+/// A date which is always written as a `<dateTime.iso8601>` value. See
+/// [as_datetime] for a version which can be used on fields directly.
+/// ```
+/// use serde_xmlrpc::DateTime;
+///
+/// let body = serde_xmlrpc::value_to_string(DateTime("19980717T14:08:55".to_string())).unwrap();
+/// assert_eq!(body, "<value><dateTime.iso8601>19980717T14:08:55</dateTime.iso8601></value>");
+///
+/// let parsed: DateTime<iso8601::DateTime> = serde_xmlrpc::value_from_str(&body).unwrap();
+/// assert_eq!(parsed.0, iso8601::datetime("19980717T14:08:55").unwrap());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DateTime<T = iso8601::DateTime>(pub T);
+
+impl<T> Serialize for DateTime<T>
+where
+    T: DateTimeField,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        as_datetime::serialize(&self.0, serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for DateTime<T>
+where
+    T: DateTimeField,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        as_datetime::deserialize(deserializer).map(DateTime)
+    }
+}
+
+/// A value which is always written as `<nil/>`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Nil;
+
+impl Serialize for Nil {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(NIL_TOKEN, &())
+    }
+}
+
+impl<'de> Deserialize<'de> for Nil {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct NilVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for NilVisitor {
+            type Value = Nil;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("nil")
+            }
+
+            fn visit_unit<E>(self) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(Nil)
+            }
+
+            fn visit_none<E>(self) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(Nil)
+            }
+        }
+
+        deserializer.deserialize_unit(NilVisitor)
+    }
+}
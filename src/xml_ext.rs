@@ -1,20 +1,108 @@
+use std::borrow::Cow;
+
+use quick_xml::escape::EscapeError;
 use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
-use quick_xml::name::QName;
-use quick_xml::{Reader, Writer};
+use quick_xml::name::{LocalName, QName};
+use quick_xml::Writer;
 
 use crate::error::{DecodingError, EncodingError, Result};
+use crate::read::XmlRead;
+
+/// The namespace of the Apache ws-xmlrpc extension types.
+pub(crate) const EXTENSIONS_NS: &str = "http://ws.apache.org/xmlrpc/namespaces/extensions";
 
-pub(crate) trait ReaderExt {
+pub(crate) trait ReaderExt<'de> {
+    fn extension_name<'n>(&self, name: QName<'n>) -> Option<LocalName<'n>>;
+    fn read_tag_event(&mut self) -> Result<Event<'de>>;
     fn expect_tag(&mut self, end: QName) -> Result<()>;
+    fn read_leading_text(&mut self) -> Result<Cow<'de, str>>;
+    fn read_text(&mut self, end: QName) -> Result<Cow<'de, str>>;
+    fn read_trimmed_text(&mut self, end: QName) -> Result<Cow<'de, str>>;
+    fn read_to_end(&mut self, end: QName) -> Result<()>;
+    fn expect_end(&mut self, end: QName) -> Result<()>;
+    fn expect_eof(&mut self) -> Result<()>;
+}
+
+/// Describes an event for error messages.
+pub(crate) fn describe(event: &Event) -> String {
+    match event {
+        Event::Start(e) => format!("<{}>", String::from_utf8_lossy(e.name().into_inner())),
+        Event::End(e) => format!("</{}>", String::from_utf8_lossy(e.name().into_inner())),
+        Event::Empty(e) => format!("<{}/>", String::from_utf8_lossy(e.name().into_inner())),
+        Event::Text(e) => format!("text {:?}", String::from_utf8_lossy(e)),
+        Event::CData(_) => "CDATA section".into(),
+        Event::Comment(_) => "comment".into(),
+        Event::Decl(_) => "XML declaration".into(),
+        Event::PI(_) => "processing instruction".into(),
+        Event::DocType(_) => "document type declaration".into(),
+        Event::Eof => "end of document".into(),
+    }
+}
+
+/// The error for an event which isn't allowed inside the `parent` element.
+pub(crate) fn unexpected(event: &Event, parent: QName) -> crate::Error {
+    DecodingError::InvalidDocument(format!(
+        "unexpected {} in <{}>",
+        describe(event),
+        String::from_utf8_lossy(parent.into_inner())
+    ))
+    .into()
+}
+
+/// Unescapes text content. Only the predefined entities and character
+/// references are resolved, anything else is an error.
+pub(crate) fn unescape<'a>(text: &BytesText<'a>) -> Result<Cow<'a, str>> {
+    text.unescape().map_err(|err| match err {
+        quick_xml::Error::EscapeError(EscapeError::UnrecognizedSymbol(_, name)) => {
+            DecodingError::UnknownEntity(name).into()
+        }
+        err => DecodingError::from(err).into(),
+    })
+}
+
+/// Returns true if the text only consists of XML whitespace characters.
+pub(crate) fn is_whitespace(text: &[u8]) -> bool {
+    text.iter()
+        .all(|b| matches!(b, b' ' | b'\t' | b'\r' | b'\n'))
 }
 
-impl<'a> ReaderExt for Reader<&'a [u8]> {
+impl<'de, R> ReaderExt<'de> for R
+where
+    R: XmlRead<'de> + ?Sized,
+{
+    /// Returns the local name of an element if extensions are enabled and it
+    /// belongs to the extensions namespace.
+    fn extension_name<'n>(&self, name: QName<'n>) -> Option<LocalName<'n>> {
+        if !self.options().extensions {
+            return None;
+        }
+
+        let (local, prefix) = name.decompose();
+        let prefix = prefix.map_or(&b""[..], |p| p.into_inner());
+        match self.resolve_namespace(prefix) {
+            Some(uri) if uri == EXTENSIONS_NS.as_bytes() => Some(local),
+            _ => None,
+        }
+    }
+
+    /// Returns the next event, skipping any whitespace-only text. This should
+    /// be used wherever only structural tags are expected, so formatting
+    /// between them is ignored.
+    fn read_tag_event(&mut self) -> Result<Event<'de>> {
+        loop {
+            match self.read_event()? {
+                Event::Text(ref e) if is_whitespace(e) => continue,
+                e => return Ok(e),
+            }
+        }
+    }
+
     fn expect_tag(&mut self, end: QName) -> Result<()> {
         loop {
-            match self.read_event() {
+            match self.read_tag_event()? {
                 // TODO: this isn't exactly right, but it's good enough for now.
-                Ok(Event::Decl(ref _d)) => continue,
-                Ok(Event::Start(ref e)) => {
+                Event::Decl(ref _d) => continue,
+                Event::Start(ref e) => {
                     if e.name() != end {
                         return Err(DecodingError::UnexpectedTag(
                             String::from_utf8_lossy(e.name().into_inner()).into(),
@@ -25,19 +113,131 @@ impl<'a> ReaderExt for Reader<&'a [u8]> {
 
                     break;
                 }
-                Ok(_e) => {
+                _e => {
                     return Err(DecodingError::UnexpectedEvent(
                         //e,
                         String::from_utf8_lossy(end.into_inner()).into(),
                     )
                     .into());
                 }
-                Err(e) => return Err(DecodingError::from(e).into()),
             };
         }
 
         Ok(())
     }
+
+    /// Reads the (unescaped) text content up to the next start or end tag,
+    /// leaving that tag to be read next. The text is returned as is, including
+    /// any whitespace.
+    fn read_leading_text(&mut self) -> Result<Cow<'de, str>> {
+        let mut text = Cow::Borrowed("");
+
+        while let Event::Text(_) = self.peek_event()? {
+            if let Event::Text(e) = self.read_event()? {
+                let unescaped = unescape(&e)?;
+                if text.is_empty() {
+                    text = unescaped;
+                } else {
+                    text.to_mut().push_str(&unescaped);
+                }
+            }
+        }
+
+        Ok(text)
+    }
+
+    /// Reads the (unescaped) text content up to the closing `end` tag. This is
+    /// supposed to be called after the matching start tag was read.
+    fn read_text(&mut self, end: QName) -> Result<Cow<'de, str>> {
+        let mut text = Cow::Borrowed("");
+
+        loop {
+            match self.read_event()? {
+                Event::Text(e) => {
+                    let unescaped = unescape(&e)?;
+                    if text.is_empty() {
+                        text = unescaped;
+                    } else {
+                        text.to_mut().push_str(&unescaped);
+                    }
+                }
+                Event::End(ref e) if e.name() == end => return Ok(text),
+                Event::Eof => {
+                    return Err(DecodingError::UnexpectedEOF(
+                        String::from_utf8_lossy(end.into_inner()).into(),
+                    )
+                    .into())
+                }
+                _ => {
+                    return Err(DecodingError::UnexpectedEvent(
+                        String::from_utf8_lossy(end.into_inner()).into(),
+                    )
+                    .into())
+                }
+            }
+        }
+    }
+
+    /// Reads the text content like [read_text](ReaderExt::read_text), with any
+    /// whitespace around it removed. This is used for numbers, booleans and
+    /// other values where that whitespace is not significant.
+    fn read_trimmed_text(&mut self, end: QName) -> Result<Cow<'de, str>> {
+        let is_space = |c: char| matches!(c, ' ' | '\t' | '\r' | '\n');
+        Ok(match self.read_text(end)? {
+            Cow::Borrowed(text) => Cow::Borrowed(text.trim_matches(is_space)),
+            Cow::Owned(text) => Cow::Owned(text.trim_matches(is_space).to_owned()),
+        })
+    }
+
+    /// Skips everything up to and including the closing `end` tag. This is
+    /// supposed to be called after the matching start tag was read. When
+    /// validating, only whitespace may be skipped.
+    fn read_to_end(&mut self, end: QName) -> Result<()> {
+        if self.options().validate {
+            return self.expect_end(end);
+        }
+
+        let mut depth = 0;
+
+        loop {
+            match self.read_event()? {
+                Event::Start(ref e) if e.name() == end => depth += 1,
+                Event::End(ref e) if e.name() == end => {
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                    depth -= 1;
+                }
+                Event::Eof => {
+                    return Err(DecodingError::UnexpectedEOF(
+                        String::from_utf8_lossy(end.into_inner()).into(),
+                    )
+                    .into())
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Reads the closing `end` tag, which may only be preceded by whitespace.
+    fn expect_end(&mut self, end: QName) -> Result<()> {
+        match self.read_tag_event()? {
+            Event::End(ref e) if e.name() == end => Ok(()),
+            e => Err(unexpected(&e, end)),
+        }
+    }
+
+    /// Makes sure nothing but whitespace follows the document element.
+    fn expect_eof(&mut self) -> Result<()> {
+        match self.read_tag_event()? {
+            Event::Eof => Ok(()),
+            e => Err(DecodingError::InvalidDocument(format!(
+                "unexpected {} after the document element",
+                describe(&e)
+            ))
+            .into()),
+        }
+    }
 }
 
 pub(crate) trait WriterExt {
@@ -57,7 +257,7 @@ pub(crate) trait WriterExt {
     }
 
     // Building blocks
-    fn write_decl(&mut self) -> Result<()>;
+    fn write_decl(&mut self, encoding: Option<&str>) -> Result<()>;
     fn write_start_tag(&mut self, tag: &str) -> Result<()>;
     fn write_end_tag(&mut self, tag: &str) -> Result<()>;
     fn write_text(&mut self, text: &str) -> Result<()>;
@@ -68,8 +268,8 @@ impl<W> WriterExt for Writer<W>
 where
     W: std::io::Write,
 {
-    fn write_decl(&mut self) -> Result<()> {
-        self.write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))
+    fn write_decl(&mut self, encoding: Option<&str>) -> Result<()> {
+        self.write_event(Event::Decl(BytesDecl::new("1.0", encoding, None)))
             .map_err(EncodingError::from)?;
         Ok(())
     }